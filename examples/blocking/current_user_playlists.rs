@@ -34,8 +34,16 @@ fn main() {
             let spotify = Spotify::default()
                 .client_credentials_manager(client_credential)
                 .build();
-            let playlists = spotify.current_user_playlists(10, None);
-            println!("{:?}", playlists);
+            match spotify.current_user_playlists(10, None) {
+                Ok(playlists) => println!(
+                    "showing {}-{} of {} playlists: {:?}",
+                    playlists.offset,
+                    playlists.offset + playlists.items.len() as u32,
+                    playlists.total,
+                    playlists.items
+                ),
+                Err(err) => println!("{:?}", err),
+            }
         }
         None => println!("auth failed"),
     };
@@ -33,7 +33,7 @@ fn main() {
             let spotify = Spotify::default()
                 .client_credentials_manager(client_credential)
                 .build();
-            let history = spotify.current_user_recently_played(10);
+            let history = spotify.current_user_recently_played(10, None);
             println!("{:?}", history);
         }
         None => println!("auth failed"),
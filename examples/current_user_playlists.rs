@@ -35,8 +35,16 @@ async fn main() {
             let spotify = Spotify::default()
                 .client_credentials_manager(client_credential)
                 .build();
-            let playlists = spotify.current_user_playlists(10, None).await;
-            println!("{:?}", playlists);
+            match spotify.current_user_playlists(10, None).await {
+                Ok(playlists) => println!(
+                    "showing {}-{} of {} playlists: {:?}",
+                    playlists.offset,
+                    playlists.offset + playlists.items.len() as u32,
+                    playlists.total,
+                    playlists.items
+                ),
+                Err(err) => println!("{:?}", err),
+            }
         }
         None => println!("auth failed"),
     };
@@ -34,7 +34,7 @@ async fn main() {
             let spotify = Spotify::default()
                 .client_credentials_manager(client_credential)
                 .build();
-            let history = spotify.current_user_recently_played(10).await;
+            let history = spotify.current_user_recently_played(10, None).await;
             println!("{:?}", history);
         }
         None => println!("auth failed"),
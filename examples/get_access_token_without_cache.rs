@@ -44,10 +44,11 @@ async fn main() {
                 .refresh_access_token_without_cache(&token_info.refresh_token.unwrap())
                 .await
             {
-                Some(refresh_token) => {
+                Ok(Some(refresh_token)) => {
                     println!("refresh token: {:?}", refresh_token);
                 }
-                None => println!("refresh token failed"),
+                Ok(None) => println!("refresh token failed"),
+                Err(err) => println!("refresh token failed: {}", err),
             }
         }
         None => println!("auth failed"),
@@ -2,41 +2,65 @@
 // 3rd-part library
 use chrono::prelude::*;
 use reqwest::blocking::Client;
-use reqwest::header::{HeaderMap, AUTHORIZATION, CONTENT_TYPE};
+use reqwest::header::{HeaderMap, ACCEPT, AUTHORIZATION, CONTENT_TYPE};
 use reqwest::Method;
 use reqwest::StatusCode;
-use serde::de::Deserialize;
+use serde::de::{Deserialize, DeserializeOwned};
 use serde_json::map::Map;
 use serde_json::Value;
 
 //  built-in battery
-use std::borrow::Cow;
+use std::cmp;
 use std::collections::HashMap;
 use std::fmt;
-use std::io::Read;
+use std::io::{self, Read, Write};
+use std::ops::ControlFlow;
 use std::string::String;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use crate::blocking::oauth2::SpotifyClientCredentials;
-use crate::blocking::util::convert_map_to_string;
-use crate::model::album::{FullAlbum, FullAlbums, PageSimpliedAlbums, SavedAlbum, SimplifiedAlbum};
-use crate::model::artist::{CursorPageFullArtists, FullArtist, FullArtists};
-use crate::model::audio::{AudioAnalysis, AudioFeatures, AudioFeaturesPayload};
+use crate::blocking::oauth2::{SpotifyClientCredentials, SpotifyOAuth, TokenInfo};
+use crate::blocking::util::{export_ndjson, warn_if_in_async_runtime};
+use crate::client::{
+    endpoint_url, may_retry, rank_genres, record_queued, remove_tracks_payload, BuildError,
+    Interceptor, Metrics, MetricsHook, QueuedRecord, RecentlyQueued, RequestInterceptor,
+    DEFAULT_MAX_BODY_SIZE, PLAYLIST_DESCRIPTION_MAX_LEN, PLAYLIST_NAME_MAX_LEN, SEARCH_MAX_OFFSET,
+};
+use crate::model::album::{FullAlbum, PageSimpliedAlbums, SavedAlbum, SimplifiedAlbum};
+use crate::model::artist::{ArtistPage, CursorPageFullArtists, FullArtist, FullArtists};
+use crate::model::audio::{AudioAnalysis, AudioFeature, AudioFeatures, AudioFeaturesPayload};
 use crate::model::category::PageCategory;
-use crate::model::context::{CurrentlyPlaybackContext, CurrentlyPlayingContext};
+use crate::model::context::{
+    CurrentUserQueue, CurrentlyPlaybackContext, CurrentlyPlayingContext, PlaybackSnapshot,
+};
 use crate::model::cud_result::CUDResult;
-use crate::model::device::DevicePayload;
+use crate::model::device::{Device, DevicePayload};
 use crate::model::page::{CursorBasedPage, Page};
-use crate::model::playing::{PlayHistory, Playing};
-use crate::model::playlist::{FeaturedPlaylists, FullPlaylist, PlaylistTrack, SimplifiedPlaylist};
-use crate::model::recommend::Recommendations;
-use crate::model::search::SearchResult;
+use crate::model::playing::{PlayHistory, Playing, TimeLimits};
+use crate::model::playlist::{
+    FeaturedPlaylists, FullPlaylist, PlaylistTrack, PlaylistTracksSnapshot, SimplifiedPlaylist,
+};
+use crate::model::recommend::{
+    RecommendationRadio, RecommendationSeeds, Recommendations, MAX_SEEDS,
+};
+use crate::model::search::{SearchAll, SearchResult};
 use crate::model::show::{
-    FullEpisode, FullShow, SeveralEpisodes, SeversalSimplifiedShows, Show, SimplifiedEpisode,
+    FullEpisode, FullShow, SavedEpisode, SeveralEpisodes, SeversalSimplifiedShows, Show,
+    SimplifiedEpisode, SimplifiedShow,
 };
-use crate::model::track::{FullTrack, FullTracks, SavedTrack, SimplifiedTrack};
+use crate::model::track::{AddReport, FullTrack, FullTracks, SavedTrack, SimplifiedTrack};
 use crate::model::user::{PrivateUser, PublicUser};
+use crate::model::PlayingItem;
 use crate::senum::{
-    AdditionalType, AlbumType, Country, IncludeExternal, RepeatState, SearchType, TimeRange, Type,
+    AdditionalType, AlbumDedup, AlbumType, Confirm, Country, IncludeExternal, RepeatState,
+    SearchType, TimeRange, Type,
+};
+use crate::util::{
+    append_query, check_recommendation_attribute, dedup_albums, encode_query, encode_query_value,
+    fields_with, json_path_at, reorder_operations, sort_by_feature, take_until,
+    with_missing_params,
 };
 lazy_static! {
     /// HTTP Client
@@ -110,12 +134,380 @@ impl From<reqwest::blocking::Response> for ApiError {
         }
     }
 }
+/// Describes errors of a request, telling apart a request that never got an
+/// answer (worth retrying) from one Spotify answered with an error status
+#[derive(Debug)]
+pub enum ClientError {
+    /// The request failed before a response arrived: DNS resolution,
+    /// connection refused, TLS handshake, timeouts...
+    Network(reqwest::Error),
+    /// Spotify answered with a non-success status
+    Http { status: u16, error: ApiError },
+    /// The token is missing scopes the endpoint needs, so the request wasn't
+    /// sent
+    InsufficientScopes(Vec<String>),
+    /// The user has no device playback could be started on
+    NoDevicesAvailable,
+    /// A parameter Spotify would reject, caught before sending the request
+    InvalidParameter(String),
+    /// The change is only allowed to the owner of the playlist, who is
+    /// someone else
+    NotPlaylistOwner {
+        playlist_id: String,
+        owner_id: String,
+    },
+    /// A successful response that isn't JSON, typically a login page served
+    /// by a proxy or captive portal instead of Spotify's answer
+    UnexpectedContentType {
+        content_type: String,
+        body_snippet: String,
+    },
+    /// The body of a request is larger than `Spotify::max_body_size`, so the
+    /// request wasn't sent
+    BodyTooLarge { size: usize, max: usize },
+    /// The body of a response is larger than `Spotify::max_response_size`,
+    /// so reading it was given up
+    ResponseTooLarge { max: usize },
+    /// The body of a response couldn't be decoded, typically a gzip stream
+    /// that is corrupt or cut short
+    Decompression(reqwest::Error),
+    /// The number of requests set with `Spotify::request_budget` was sent,
+    /// so the request wasn't
+    BudgetExhausted,
+}
+impl failure::Fail for ClientError {
+    fn cause(&self) -> Option<&dyn failure::Fail> {
+        match self {
+            ClientError::Network(e) => Some(e),
+            ClientError::Decompression(e) => Some(e),
+            ClientError::Http { error, .. } => Some(error),
+            ClientError::InsufficientScopes(_)
+            | ClientError::NoDevicesAvailable
+            | ClientError::InvalidParameter(_)
+            | ClientError::NotPlaylistOwner { .. }
+            | ClientError::UnexpectedContentType { .. }
+            | ClientError::BodyTooLarge { .. }
+            | ClientError::ResponseTooLarge { .. }
+            | ClientError::BudgetExhausted => None,
+        }
+    }
+}
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ClientError::Network(e) => write!(f, "Failed to reach Spotify API: {}", e),
+            ClientError::Http { status, error } => write!(f, "HTTP status {}: {}", status, error),
+            ClientError::InsufficientScopes(scopes) => {
+                write!(f, "Missing scopes: {}", scopes.join(", "))
+            }
+            ClientError::NoDevicesAvailable => write!(f, "No devices available"),
+            ClientError::InvalidParameter(message) => write!(f, "Invalid parameter: {}", message),
+            ClientError::NotPlaylistOwner {
+                playlist_id,
+                owner_id,
+            } => write!(
+                f,
+                "Playlist {} is owned by {}, only its owner can change it",
+                playlist_id, owner_id
+            ),
+            ClientError::UnexpectedContentType {
+                content_type,
+                body_snippet,
+            } => write!(f, "Expected JSON, got {}: {}", content_type, body_snippet),
+            ClientError::BodyTooLarge { size, max } => write!(
+                f,
+                "Request body of {} bytes is larger than the maximum of {} bytes",
+                size, max
+            ),
+            ClientError::ResponseTooLarge { max } => write!(
+                f,
+                "Response body is larger than the maximum of {} bytes",
+                max
+            ),
+            ClientError::Decompression(e) => write!(f, "Failed to decode response body: {}", e),
+            ClientError::BudgetExhausted => write!(f, "The request budget is exhausted"),
+        }
+    }
+}
+/// Body of a transfer playback request: `device_ids` must be an array, even
+/// though it only takes a single device
+fn transfer_playback_payload(device_id: &str, play: bool) -> Value {
+    json!({
+        "device_ids": [device_id],
+        "play": play,
+    })
+}
+/// Collaborative playlists must be private, so asking for both is rejected
+/// before sending the request
+fn check_collaborative_visibility(
+    public: Option<bool>,
+    collaborative: Option<bool>,
+) -> Result<(), ClientError> {
+    if public == Some(true) && collaborative == Some(true) {
+        return Err(ClientError::InvalidParameter(
+            "a collaborative playlist can't be public".to_owned(),
+        ));
+    }
+    Ok(())
+}
+/// Playlist names must not be blank, and names and descriptions must fit
+/// Spotify's limits, checked before sending the request
+fn check_playlist_details(
+    name: Option<&str>,
+    description: Option<&str>,
+) -> Result<(), ClientError> {
+    if let Some(name) = name {
+        if name.trim().is_empty() {
+            return Err(ClientError::InvalidParameter(
+                "the playlist name is empty".to_owned(),
+            ));
+        }
+        if name.chars().count() > PLAYLIST_NAME_MAX_LEN {
+            return Err(ClientError::InvalidParameter(format!(
+                "the playlist name is longer than {} characters",
+                PLAYLIST_NAME_MAX_LEN
+            )));
+        }
+    }
+    if let Some(description) = description {
+        if description.chars().count() > PLAYLIST_DESCRIPTION_MAX_LEN {
+            return Err(ClientError::InvalidParameter(format!(
+                "the playlist description is longer than {} characters",
+                PLAYLIST_DESCRIPTION_MAX_LEN
+            )));
+        }
+    }
+    Ok(())
+}
+/// HTTP client with the given timeouts, reqwest's defaults for the ones left
+/// out
+fn http_client(
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+) -> Result<Client, reqwest::Error> {
+    let mut builder = Client::builder();
+    if let Some(timeout) = timeout {
+        builder = builder.timeout(timeout);
+    }
+    if let Some(connect_timeout) = connect_timeout {
+        builder = builder.connect_timeout(connect_timeout);
+    }
+    builder.build()
+}
+fn default_max_body_size() -> usize {
+    DEFAULT_MAX_BODY_SIZE
+}
+/// Serialize the body of a request, refusing it above `max` bytes
+fn serialize_body(payload: &Value, max: usize) -> Result<Vec<u8>, failure::Error> {
+    let body = serde_json::to_vec(payload)?;
+    if body.len() > max {
+        return Err(ClientError::BodyTooLarge {
+            size: body.len(),
+            max,
+        }
+        .into());
+    }
+    Ok(body)
+}
+/// Refuse a response of `size` bytes when it is above `max`
+fn check_response_size(size: u64, max: Option<usize>) -> Result<(), ClientError> {
+    match max {
+        Some(max) if size > max as u64 => Err(ClientError::ResponseTooLarge { max }),
+        _ => Ok(()),
+    }
+}
+/// Error reading the body of a response through `Read`, which wraps the
+/// errors of reqwest in an `io::Error`
+fn read_error(e: io::Error) -> failure::Error {
+    if e.get_ref().is_none() {
+        return e.into();
+    }
+    match e
+        .into_inner()
+        .map(|inner| inner.downcast::<reqwest::Error>())
+    {
+        Some(Ok(e)) => body_error(*e).into(),
+        Some(Err(inner)) => format_err!("failed to read response: {}", inner),
+        None => unreachable!(),
+    }
+}
+/// Error reading the body of a response: decoding errors are told apart
+/// from network ones, a read timeout is reported as a decoding error by
+/// reqwest too
+fn body_error(e: reqwest::Error) -> ClientError {
+    if e.is_decode() && !e.is_timeout() {
+        ClientError::Decompression(e)
+    } else {
+        ClientError::Network(e)
+    }
+}
+/// Re-types the `InvalidParameter` of the checks shared with the async
+/// client, such as `util::check_recommendation_attribute` and
+/// `RecommendationSeeds::check`, as this client's
+fn invalid_parameter(e: failure::Error) -> failure::Error {
+    match e.downcast::<crate::client::ClientError>() {
+        Ok(crate::client::ClientError::InvalidParameter(message)) => {
+            ClientError::InvalidParameter(message).into()
+        }
+        Ok(e) => e.into(),
+        Err(e) => e,
+    }
+}
+/// Whether the request that failed with `e` provably had no effect: it
+/// wasn't sent, couldn't connect, or Spotify answered with an error status.
+/// After a timeout or a reset it may have been handled, so it's not.
+fn not_delivered(e: &failure::Error) -> bool {
+    match e.downcast_ref::<ClientError>() {
+        Some(ClientError::Network(e)) => e.is_connect(),
+        Some(ClientError::Http { .. })
+        | Some(ClientError::InsufficientScopes(_))
+        | Some(ClientError::InvalidParameter(_))
+        | Some(ClientError::BodyTooLarge { .. })
+        | Some(ClientError::BudgetExhausted) => true,
+        _ => false,
+    }
+}
+/// Headers of an API request: the bearer token, an explicit `Accept` so that
+/// proxies and mocks negotiate JSON and, for the methods sending a JSON body,
+/// its `Content-Type`
+fn request_headers(auth: &str, has_body: bool) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(AUTHORIZATION, auth.parse().unwrap());
+    headers.insert(ACCEPT, "application/json".parse().unwrap());
+    if has_body {
+        headers.insert(CONTENT_TYPE, "application/json".parse().unwrap());
+    }
+    headers
+}
+/// Number of characters of an unexpected response kept in the error
+const BODY_SNIPPET_LEN: usize = 200;
+/// Non-empty bodies of successful responses must be JSON; a missing
+/// `Content-Type` is given the benefit of the doubt
+fn check_content_type(content_type: Option<&str>, body: &str) -> Result<(), ClientError> {
+    let content_type = match content_type {
+        Some(content_type) if !body.trim().is_empty() => content_type,
+        _ => return Ok(()),
+    };
+    let media_type = content_type
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_lowercase();
+    if media_type == "application/json" || media_type.ends_with("+json") {
+        return Ok(());
+    }
+    Err(ClientError::UnexpectedContentType {
+        content_type: content_type.to_owned(),
+        body_snippet: body.chars().take(BODY_SNIPPET_LEN).collect(),
+    })
+}
+/// Position in milliseconds of `fraction` (clamped to [0, 1]) of `duration_ms`
+fn fraction_to_position_ms(fraction: f64, duration_ms: u32) -> u32 {
+    let fraction = if fraction.is_nan() {
+        0.0
+    } else {
+        fraction.clamp(0.0, 1.0)
+    };
+    (fraction * f64::from(duration_ms)).round() as u32
+}
+
+/// The items of every page of an endpoint paged by offset, in order, for the
+/// `*_all` methods: `page` is called with the offset of the first item not
+/// read yet, from 0 until a page is the last one or is empty. A page is only
+/// requested once the items of the previous one were consumed, so stopping
+/// early saves the remaining requests.
+fn fetch_all_pages<T, P>(mut page: P) -> impl Iterator<Item = Result<T, failure::Error>>
+where
+    P: FnMut(u32) -> Result<Page<T>, failure::Error>,
+{
+    let mut items = Vec::new().into_iter();
+    let mut offset = Some(0);
+    std::iter::from_fn(move || loop {
+        if let Some(item) = items.next() {
+            return Some(Ok(item));
+        }
+        let current = offset?;
+        match page(current) {
+            Ok(next_page) => {
+                offset = if next_page.next.is_none() || next_page.items.is_empty() {
+                    None
+                } else {
+                    Some(current + next_page.items.len() as u32)
+                };
+                items = next_page.items.into_iter();
+            }
+            Err(e) => {
+                offset = None;
+                return Some(Err(e));
+            }
+        }
+    })
+}
+/// A client authorized by a user, waiting for their token, see
+/// `Spotify::from_env`
+#[derive(Clone, Debug)]
+pub struct UserClientBuilder {
+    /// The authorization flow to get the user's token with
+    pub oauth: SpotifyOAuth,
+}
+impl UserClientBuilder {
+    /// Build the client with the user's token, obtained through `oauth`
+    pub fn build(&self, token_info: TokenInfo) -> Spotify {
+        let client_credential = SpotifyClientCredentials::default()
+            .client_id(&self.oauth.client_id)
+            .client_secret(&self.oauth.client_secret)
+            .token_info(token_info)
+            .build();
+        Spotify::default()
+            .client_credentials_manager(client_credential)
+            .build()
+    }
+}
 /// Spotify API object
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Spotify {
     pub prefix: String,
     pub access_token: Option<String>,
     pub client_credentials_manager: Option<SpotifyClientCredentials>,
+    pub default_market: Option<Country>,
+    /// Profile of the user the token belongs to, see `current_user_cached`
+    #[serde(skip)]
+    user_cache: Arc<Mutex<Option<PrivateUser>>>,
+    #[serde(skip)]
+    interceptors: Vec<Interceptor>,
+    #[serde(skip)]
+    metrics: Option<MetricsHook>,
+    /// Time allowed for a whole request, see `timeout`
+    #[serde(skip)]
+    timeout: Option<Duration>,
+    /// Time allowed for connecting to Spotify, see `connect_timeout`
+    #[serde(skip)]
+    connect_timeout: Option<Duration>,
+    /// HTTP client built for the timeouts, the shared `CLIENT` otherwise
+    #[serde(skip)]
+    http: Option<Client>,
+    /// Largest request body sent, see `max_body_size`
+    #[serde(skip, default = "default_max_body_size")]
+    max_body_size: usize,
+    /// Largest response body read, see `max_response_size`
+    #[serde(skip)]
+    max_response_size: Option<usize>,
+    /// See `queue_dedup_window`
+    #[serde(skip)]
+    queue_dedup_window: Option<Duration>,
+    /// How many times a request failing on the network is sent again, see
+    /// `network_retries`
+    #[serde(skip)]
+    network_retries: u32,
+    #[serde(skip)]
+    network_retry_delay: Duration,
+    /// Requests left to send, shared with clones, see `request_budget`
+    #[serde(skip)]
+    request_budget: Option<Arc<AtomicUsize>>,
+    /// When each item was last queued, by device, see `queue_dedup_window`
+    #[serde(skip)]
+    recently_queued: Arc<Mutex<RecentlyQueued>>,
 }
 impl Spotify {
     //! If you want to check examples of all API endpoint, you could check the
@@ -125,9 +517,48 @@ impl Spotify {
             prefix: "https://api.spotify.com/v1/".to_owned(),
             access_token: None,
             client_credentials_manager: None,
+            default_market: None,
+            user_cache: Arc::new(Mutex::new(None)),
+            interceptors: Vec::new(),
+            metrics: None,
+            timeout: None,
+            connect_timeout: None,
+            http: None,
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+            max_response_size: None,
+            queue_dedup_window: None,
+            network_retries: 0,
+            network_retry_delay: Duration::default(),
+            request_budget: None,
+            recently_queued: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
+    /// Prepare a client authorized by a user, from the `RSPOTIFY_CLIENT_ID`,
+    /// `RSPOTIFY_CLIENT_SECRET` and `RSPOTIFY_REDIRECT_URI` environment
+    /// variables (a `.env` file works too) and the optional space-delimited
+    /// `RSPOTIFY_SCOPES`, see `SpotifyOAuth::from_env`. Fails naming the
+    /// variables that are missing. Nothing is asked to the user yet: get
+    /// their token with the returned `oauth`, e.g. with `util::get_token`,
+    /// and `build` the client with it. For an app-only client, see
+    /// `app_from_env`.
+    pub fn from_env() -> Result<UserClientBuilder, failure::Error> {
+        Ok(UserClientBuilder {
+            oauth: SpotifyOAuth::from_env()?,
+        })
+    }
+
+    /// Build an app-only client from the `RSPOTIFY_CLIENT_ID` and
+    /// `RSPOTIFY_CLIENT_SECRET` environment variables (a `.env` file works
+    /// too), using the client-credentials flow, which can't access user data.
+    /// Fails naming the variables that are missing.
+    pub fn app_from_env() -> Result<Spotify, failure::Error> {
+        let client_credential = SpotifyClientCredentials::from_env()?.build();
+        Ok(Spotify::default()
+            .client_credentials_manager(client_credential)
+            .build())
+    }
+
     // pub fn prefix(mut self, prefix: &str) -> Spotify {
     pub fn prefix(mut self, prefix: &str) -> Spotify {
         self.prefix = prefix.to_owned();
@@ -147,24 +578,191 @@ impl Spotify {
         self
     }
 
+    /// Market used by search, browse and catalog endpoints when no market
+    /// (or country) is passed explicitly
+    pub fn default_market(mut self, market: Country) -> Spotify {
+        self.default_market = Some(market);
+        self
+    }
+
+    /// Switch to another access token, e.g. to act as another user. The
+    /// cached current user is dropped, and clones made before keep theirs,
+    /// so no user's data is returned on behalf of another.
+    /// To keep the cache across a refresh, see `set_user_token`.
+    pub fn set_token(&mut self, access_token: &str) {
+        self.access_token = Some(access_token.to_owned());
+        self.user_cache = Arc::new(Mutex::new(None));
+        self.recently_queued = Arc::new(Mutex::new(HashMap::new()));
+    }
+
+    /// Switch to `access_token` of the user `user_id`, e.g. once it's
+    /// refreshed. The cached current user, and so their country, is kept
+    /// when it's the same user, so a refresh doesn't request the profile
+    /// again; for another user it's dropped as with `set_token`.
+    pub fn set_user_token(&mut self, access_token: &str, user_id: &str) {
+        let same_user = matches!(
+            &*self.user_cache.lock().unwrap(),
+            Some(user) if user.id == user_id
+        );
+        if same_user {
+            self.access_token = Some(access_token.to_owned());
+        } else {
+            self.set_token(access_token);
+        }
+    }
+
+    /// Run `interceptor` on every request before it's sent, after the
+    /// interceptors registered before it
+    pub fn interceptor<I: RequestInterceptor + 'static>(mut self, interceptor: I) -> Spotify {
+        self.interceptors.push(Interceptor(Arc::new(interceptor)));
+        self
+    }
+
+    /// Report the requests made, their responses and retries to `metrics`
+    pub fn metrics<M: Metrics + 'static>(mut self, metrics: M) -> Spotify {
+        self.metrics = Some(MetricsHook(Arc::new(metrics)));
+        self
+    }
+
+    /// Give up on requests that take longer than `timeout` in total,
+    /// including reading the response. Like `connect_timeout`, it takes
+    /// effect once the client is built.
+    pub fn timeout(mut self, timeout: Duration) -> Spotify {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Give up on requests that can't connect to Spotify within
+    /// `connect_timeout`, to fail fast when it is unreachable while still
+    /// allowing a longer `timeout` for reading big responses.
+    pub fn connect_timeout(mut self, connect_timeout: Duration) -> Spotify {
+        self.connect_timeout = Some(connect_timeout);
+        self
+    }
+
+    /// Refuse to send request bodies larger than `max_body_size` bytes,
+    /// failing with `ClientError::BodyTooLarge` instead, e.g. to catch a
+    /// misconfigured batch size early. Defaults to `DEFAULT_MAX_BODY_SIZE`.
+    pub fn max_body_size(mut self, max_body_size: usize) -> Spotify {
+        self.max_body_size = max_body_size;
+        self
+    }
+
+    /// Stop reading response bodies larger than `max_response_size` bytes,
+    /// failing with `ClientError::ResponseTooLarge` instead, so that a
+    /// misbehaving proxy can't exhaust memory. Unlimited by default.
+    pub fn max_response_size(mut self, max_response_size: usize) -> Spotify {
+        self.max_response_size = Some(max_response_size);
+        self
+    }
+
+    /// Skip adding an item to the queue of a device when it was already
+    /// added there less than `window` ago, by this client or its clones, so
+    /// that a double call or a retry doesn't queue it twice. Off by default,
+    /// as queueing the same item twice on purpose is allowed by Spotify.
+    pub fn queue_dedup_window(mut self, window: Duration) -> Spotify {
+        self.queue_dedup_window = Some(window);
+        self
+    }
+
+    /// Send a request again, up to `retries` times and `delay` apart, when it
+    /// fails before Spotify answers, e.g. on a connection reset. Requests
+    /// Spotify answered with an error status aren't retried. Requests that
+    /// aren't idempotent, i.e. POSTs, playlist reorders and removals by
+    /// position sent without a `snapshot_id`, and `put_raw` and `delete_raw`,
+    /// are only retried when they couldn't connect at all, as they may have
+    /// been handled otherwise. No retries by default.
+    pub fn network_retries(mut self, retries: u32, delay: Duration) -> Spotify {
+        self.network_retries = retries;
+        self.network_retry_delay = delay;
+        self
+    }
+
+    /// Send at most `budget` requests, retries included, failing any
+    /// request after that with `ClientError::BudgetExhausted`: a ceiling
+    /// for batch jobs, should paging run away. Clones share the budget.
+    /// Unlimited by default.
+    pub fn request_budget(mut self, budget: usize) -> Spotify {
+        self.request_budget = Some(Arc::new(AtomicUsize::new(budget)));
+        self
+    }
+
+    /// Requests left to send, `None` without a `request_budget`
+    pub fn remaining_requests(&self) -> Option<usize> {
+        self.request_budget
+            .as_ref()
+            .map(|remaining| remaining.load(Ordering::SeqCst))
+    }
+
+    /// Take a request out of the budget, if there's one
+    fn spend_request(&self) -> Result<(), ClientError> {
+        match self.request_budget {
+            Some(ref remaining) => remaining
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                .map(|_| ())
+                .map_err(|_| ClientError::BudgetExhausted),
+            None => Ok(()),
+        }
+    }
+
+    /// Run `record` on the metrics hooks, if any
+    fn record_metrics<F: FnOnce(&dyn Metrics)>(&self, record: F) {
+        if let Some(ref metrics) = self.metrics {
+            record(&*metrics.0);
+        }
+    }
+
+    /// Run the interceptors on the headers of a request, keeping its
+    /// `Authorization` header
+    fn intercept(&self, method: &Method, url: &str, headers: &mut HeaderMap) {
+        if self.interceptors.is_empty() {
+            return;
+        }
+        let authorization = headers.get(AUTHORIZATION).cloned();
+        for interceptor in &self.interceptors {
+            interceptor.0.intercept(method, url, headers);
+        }
+        match authorization {
+            Some(authorization) => headers.insert(AUTHORIZATION, authorization),
+            None => headers.remove(AUTHORIZATION),
+        };
+    }
+
+    /// Check the client and build its HTTP client, panicking when either
+    /// fails. See `try_build` to handle the errors.
     pub fn build(self) -> Spotify {
+        match self.try_build() {
+            Ok(spotify) => spotify,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Check the client and build its HTTP client, e.g. for the timeouts,
+    /// so that misconfiguration is reported here rather than by the first
+    /// request
+    pub fn try_build(mut self) -> Result<Spotify, BuildError> {
         if self.access_token.is_none() && self.client_credentials_manager.is_none() {
-            panic!("access_token and client_credentials_manager are none!!!");
+            return Err(BuildError::MissingCredentials);
         }
-        self
+        if self.timeout.is_some() || self.connect_timeout.is_some() {
+            let http =
+                http_client(self.timeout, self.connect_timeout).map_err(BuildError::Transport)?;
+            self.http = Some(http);
+        }
+        Ok(self)
     }
 
-    fn auth_headers(&self) -> String {
+    fn auth_headers(&self) -> Result<String, failure::Error> {
         let token = match self.access_token {
             Some(ref token) => token.to_owned(),
             None => match self.client_credentials_manager {
                 Some(ref client_credentials_manager) => {
-                    client_credentials_manager.get_access_token()
+                    client_credentials_manager.get_access_token()?
                 }
                 None => panic!("client credentials manager is none"),
             },
         };
-        "Bearer ".to_owned() + &token
+        Ok("Bearer ".to_owned() + &token)
     }
 
     fn internal_call(
@@ -172,39 +770,132 @@ impl Spotify {
         method: Method,
         url: &str,
         payload: Option<&Value>,
+        idempotent: bool,
     ) -> Result<String, failure::Error> {
-        let mut url: Cow<str> = url.into();
-        if !url.starts_with("http") {
-            url = ["https://api.spotify.com/v1/", &url].concat().into();
-        }
+        warn_if_in_async_runtime();
+        let url = endpoint_url(&self.prefix, url).map_err(ClientError::InvalidParameter)?;
+        let body = match payload {
+            Some(payload) => {
+                let body = serialize_body(payload, self.max_body_size)?;
+                debug!("{} {} with a body of {} bytes", method, url, body.len());
+                Some(body)
+            }
+            None => None,
+        };
 
-        let mut headers = HeaderMap::new();
-        headers.insert(AUTHORIZATION, self.auth_headers().parse().unwrap());
-        headers.insert(CONTENT_TYPE, "application/json".parse().unwrap());
+        let mut headers = request_headers(&self.auth_headers()?, payload.is_some());
+        self.intercept(&method, &url, &mut headers);
 
-        let mut response = {
-            let builder = CLIENT.request(method, &url.into_owned()).headers(headers);
+        self.spend_request()?;
+        let client = self.http.as_ref().unwrap_or(&CLIENT);
+        let started = Instant::now();
+        self.record_metrics(|metrics| metrics.on_request(&method, &url));
+        let mut retries = 0;
+        let mut response = loop {
+            let builder = client
+                .request(method.clone(), url.as_ref())
+                .headers(headers.clone());
 
             // only add body if necessary
             // spotify rejects GET requests that have a body with a 400 response
-            let builder = if let Some(json) = payload {
-                builder.json(json)
+            let builder = if let Some(ref body) = body {
+                builder.body(body.clone())
             } else {
                 builder
             };
 
-            builder.send().unwrap()
+            match builder.send() {
+                Ok(response) => break response,
+                // a retry is a request too: out of budget, the failure is
+                // reported as it is
+                Err(e)
+                    if retries < self.network_retries
+                        && may_retry(idempotent, e.is_connect())
+                        && self.spend_request().is_ok() =>
+                {
+                    retries += 1;
+                    self.record_metrics(|metrics| metrics.on_retry(&method, &url, retries));
+                    warn!(
+                        "{} {} failed, retrying ({}/{}): {}",
+                        method, url, retries, self.network_retries, e
+                    );
+                    thread::sleep(self.network_retry_delay);
+                }
+                Err(e) => {
+                    let elapsed = started.elapsed();
+                    self.record_metrics(|metrics| {
+                        metrics.on_response(&method, &url, None, elapsed)
+                    });
+                    return Err(ClientError::Network(e).into());
+                }
+            }
         };
 
-        let mut buf = String::new();
-        response
-            .read_to_string(&mut buf)
-            .expect("failed to read response");
-        if response.status().is_success() {
+        let status = response.status();
+        let elapsed = started.elapsed();
+        self.record_metrics(|metrics| {
+            metrics.on_response(&method, &url, Some(status.as_u16()), elapsed)
+        });
+        if status.is_success() {
+            let content_type = response
+                .headers()
+                .get(CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_owned);
+            if let Some(length) = response.content_length() {
+                check_response_size(length, self.max_response_size)?;
+            }
+            // one byte over the maximum is enough to tell it was exceeded
+            let limit = self
+                .max_response_size
+                .map_or(u64::MAX, |max| max as u64 + 1);
+            let mut bytes = Vec::new();
+            (&mut response)
+                .take(limit)
+                .read_to_end(&mut bytes)
+                .map_err(read_error)?;
+            check_response_size(bytes.len() as u64, self.max_response_size)?;
+            let buf = String::from_utf8(bytes)?;
+            check_content_type(content_type.as_deref(), &buf)?;
             Ok(buf)
         } else {
-            Err(failure::Error::from(ApiError::from(response)))
+            Err(failure::Error::from(ClientError::Http {
+                status: status.as_u16(),
+                error: ApiError::from(response),
+            }))
+        }
+    }
+    ///Download the image at `url`, e.g. the `url` of an `Image`, as raw
+    ///bytes. Images are served by Spotify's CDN rather than the API, so the
+    ///request is sent without the `Authorization` header, which the CDN
+    ///rejects, and without running the interceptors. `max_response_size`
+    ///still applies.
+    pub fn fetch_image(&self, url: &str) -> Result<Vec<u8>, failure::Error> {
+        self.spend_request()?;
+        let client = self.http.as_ref().unwrap_or(&CLIENT);
+        let mut response = client.get(url).send().map_err(ClientError::Network)?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(ClientError::Http {
+                status: status.as_u16(),
+                error: ApiError::from(response),
+            }
+            .into());
+        }
+        if let Some(length) = response.content_length() {
+            check_response_size(length, self.max_response_size)?;
         }
+        // one byte over the maximum is enough to tell it was exceeded
+        let limit = self
+            .max_response_size
+            .map_or(u64::MAX, |max| max as u64 + 1);
+        let mut bytes = Vec::new();
+        (&mut response)
+            .take(limit)
+            .read_to_end(&mut bytes)
+            .map_err(read_error)?;
+        check_response_size(bytes.len() as u64, self.max_response_size)?;
+        Ok(bytes)
     }
     ///send get request
     fn get(
@@ -212,29 +903,81 @@ impl Spotify {
         url: &str,
         params: &mut HashMap<String, String>,
     ) -> Result<String, failure::Error> {
-        if !params.is_empty() {
-            let param: String = convert_map_to_string(params);
-            let mut url_with_params = url.to_owned();
-            url_with_params.push('?');
-            url_with_params.push_str(&param);
-            self.internal_call(Method::GET, &url_with_params, None)
-        } else {
-            self.internal_call(Method::GET, url, None)
-        }
+        // `url` may carry a query already, e.g. `next` links or repeated keys
+        let url_with_params = append_query(url, &encode_query(params));
+        self.internal_call(Method::GET, &url_with_params, None, true)
     }
 
     ///send post request
     fn post(&self, url: &str, payload: &Value) -> Result<String, failure::Error> {
-        self.internal_call(Method::POST, url, Some(payload))
+        self.internal_call(Method::POST, url, Some(payload), false)
     }
     ///send put request
     fn put(&self, url: &str, payload: &Value) -> Result<String, failure::Error> {
-        self.internal_call(Method::PUT, url, Some(payload))
+        self.internal_call(Method::PUT, url, Some(payload), true)
+    }
+    ///send a put request that mustn't be sent twice, so it's only retried
+    ///when it couldn't connect
+    fn put_once(&self, url: &str, payload: &Value) -> Result<String, failure::Error> {
+        self.internal_call(Method::PUT, url, Some(payload), false)
     }
 
     /// send delete request
     fn delete(&self, url: &str, payload: &Value) -> Result<String, failure::Error> {
-        self.internal_call(Method::DELETE, url, Some(payload))
+        self.internal_call(Method::DELETE, url, Some(payload), true)
+    }
+    ///send a delete request that mustn't be sent twice, so it's only retried
+    ///when it couldn't connect
+    fn delete_once(&self, url: &str, payload: &Value) -> Result<String, failure::Error> {
+        self.internal_call(Method::DELETE, url, Some(payload), false)
+    }
+
+    ///Send a GET request to an endpoint this client has no method for. `url`
+    ///is either relative to the API prefix (`me/tracks` or `/me/tracks`) or
+    ///an absolute URL, such as the `next` link of a `Page`, which is used as
+    ///is. Absolute URLs on another origin than the API prefix are rejected
+    ///with `ClientError::InvalidParameter`, as they'd receive the user's
+    ///token. Empty responses are returned as `Value::Null`.
+    ///Parameters:
+    ///- url - the endpoint path or absolute URL
+    ///- params - the query parameters
+    pub fn get_raw(
+        &self,
+        url: &str,
+        params: &mut HashMap<String, String>,
+    ) -> Result<Value, failure::Error> {
+        let result = self.get(url, params)?;
+        self.convert_raw_result(&result)
+    }
+
+    ///Send a POST request with a JSON body, see `get_raw`
+    pub fn post_raw(&self, url: &str, payload: &Value) -> Result<Value, failure::Error> {
+        let result = self.post(url, payload)?;
+        self.convert_raw_result(&result)
+    }
+
+    ///Send a PUT request with a JSON body, see `get_raw`. As the endpoint
+    ///may not be idempotent, it's only retried by `network_retries` when it
+    ///couldn't connect.
+    pub fn put_raw(&self, url: &str, payload: &Value) -> Result<Value, failure::Error> {
+        let result = self.put_once(url, payload)?;
+        self.convert_raw_result(&result)
+    }
+
+    ///Send a DELETE request with a JSON body, see `get_raw`. As the endpoint
+    ///may not be idempotent, it's only retried by `network_retries` when it
+    ///couldn't connect.
+    pub fn delete_raw(&self, url: &str, payload: &Value) -> Result<Value, failure::Error> {
+        let result = self.delete_once(url, payload)?;
+        self.convert_raw_result(&result)
+    }
+
+    fn convert_raw_result(&self, input: &str) -> Result<Value, failure::Error> {
+        if input.is_empty() {
+            Ok(Value::Null)
+        } else {
+            self.convert_result::<Value>(input)
+        }
     }
 
     ///[get-track](https://developer.spotify.com/web-api/get-track/)
@@ -249,7 +992,9 @@ impl Spotify {
     }
 
     ///[get-several-tracks](https://developer.spotify.com/web-api/get-several-tracks/)
-    ///returns a list of tracks given a list of track IDs, URIs, or URLs
+    ///returns a list of tracks given a list of track IDs, URIs, or URLs.
+    ///The tracks are requested 50 at a time, and the result has one element
+    ///per given ID, `None` where the ID is invalid.
     ///Parameters:
     ///- track_ids - a list of spotify URIs, URLs or IDs
     ///- market - an ISO 3166-1 alpha-2 country code.
@@ -257,20 +1002,17 @@ impl Spotify {
         &self,
         track_ids: Vec<&str>,
         market: Option<Country>,
-    ) -> Result<FullTracks, failure::Error> {
-        let mut ids: Vec<String> = vec![];
-        for track_id in track_ids {
-            ids.push(self.get_id(Type::Track, track_id));
-        }
-        let url = format!("tracks/?ids={}", ids.join(","));
-        // url.push_str(&ids.join(","));
+    ) -> Result<Vec<Option<FullTrack>>, failure::Error> {
+        let ids: Vec<String> = track_ids
+            .iter()
+            .map(|track_id| self.get_id(Type::Track, track_id))
+            .collect();
+        let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
         let mut params: HashMap<String, String> = HashMap::new();
-        if let Some(_market) = market {
+        if let Some(_market) = self.market_or_default(market)? {
             params.insert("market".to_owned(), _market.as_str().to_owned());
         }
-        trace!("{:?}", &url);
-        let result = self.get(&url, &mut params)?;
-        self.convert_result::<FullTracks>(&result)
+        self.batch_get("tracks", &ids, 50, &params)
     }
 
     ///[get-artist](https://developer.spotify.com/web-api/get-artist/)
@@ -286,18 +1028,21 @@ impl Spotify {
     }
 
     ///[get-several-artists](https://developer.spotify.com/web-api/get-several-artists/)
-    ///returns a list of artists given the artist IDs, URIs, or URLs
+    ///returns a list of artists given the artist IDs, URIs, or URLs. The
+    ///artists are requested 50 at a time (the endpoint's limit), and the
+    ///result has one element per given ID, `None` where the ID is invalid.
     ///Parameters:
     ///- artist_ids - a list of  artist IDs, URIs or URLs
-    pub fn artists(&self, artist_ids: Vec<String>) -> Result<FullArtists, failure::Error> {
-        let mut ids: Vec<String> = vec![];
-        for artist_id in artist_ids {
-            ids.push(self.get_id(Type::Artist, &artist_id));
-        }
-        let url = format!("artists/?ids={}", ids.join(","));
-        // url.push_str(&ids.join(","));
-        let result = self.get(&url, &mut HashMap::new())?;
-        self.convert_result::<FullArtists>(&result)
+    pub fn artists(
+        &self,
+        artist_ids: Vec<String>,
+    ) -> Result<Vec<Option<FullArtist>>, failure::Error> {
+        let ids: Vec<String> = artist_ids
+            .iter()
+            .map(|artist_id| self.get_id(Type::Artist, artist_id))
+            .collect();
+        let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+        self.batch_get("artists", &ids, 50, &HashMap::new())
     }
 
     ///[get-artists-albums](https://developer.spotify.com/web-api/get-artists-albums/)
@@ -325,7 +1070,7 @@ impl Spotify {
         if let Some(_offset) = offset {
             params.insert("offset".to_owned(), _offset.to_string());
         }
-        if let Some(_country) = country {
+        if let Some(_country) = self.market_or_default(country)? {
             params.insert("country".to_owned(), _country.as_str().to_owned());
         }
         let trid = self.get_id(Type::Artist, artist_id);
@@ -336,6 +1081,43 @@ impl Spotify {
         self.convert_result::<Page<SimplifiedAlbum>>(&result)
     }
 
+    ///[get-artists-albums](https://developer.spotify.com/web-api/get-artists-albums/)
+    ///Get all the albums of an artist, requesting pages of 50 until there is
+    ///no next page, then removing duplicates as chosen by `dedup`.
+    ///Parameters:
+    ///- artist_id - the artist ID, URI or URL
+    ///- include_groups - which relationships to the artist to include
+    ///  (`album`, `single`, `appears_on`, `compilation`), all of them if empty
+    ///- country - limit the response to one particular country
+    ///- dedup - how duplicated albums are removed, see `senum::AlbumDedup`
+    pub fn artist_albums_all(
+        &self,
+        artist_id: &str,
+        include_groups: &[AlbumType],
+        country: Option<Country>,
+        dedup: AlbumDedup,
+    ) -> Result<Vec<SimplifiedAlbum>, failure::Error> {
+        let trid = self.get_id(Type::Artist, artist_id);
+        let url = format!("artists/{}/albums", trid);
+        let country = self.market_or_default(country)?;
+        let include_groups: Vec<&str> = include_groups.iter().map(AlbumType::as_str).collect();
+        let albums = fetch_all_pages(|offset| {
+            let mut params = HashMap::new();
+            params.insert("limit".to_owned(), 50.to_string());
+            params.insert("offset".to_owned(), offset.to_string());
+            if !include_groups.is_empty() {
+                params.insert("include_groups".to_owned(), include_groups.join(","));
+            }
+            if let Some(_country) = country {
+                params.insert("country".to_owned(), _country.as_str().to_owned());
+            }
+            let result = self.get(&url, &mut params)?;
+            self.convert_result::<Page<SimplifiedAlbum>>(&result)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+        Ok(dedup_albums(albums, dedup))
+    }
+
     ///[get artists to tracks](https://developer.spotify.com/web-api/get-artists-top-tracks/)
     /// Get Spotify catalog information about an artist's top 10 tracks by country.
     ///    Parameters:
@@ -347,8 +1129,8 @@ impl Spotify {
         country: T,
     ) -> Result<FullTracks, failure::Error> {
         let mut params: HashMap<String, String> = HashMap::new();
-        let country = country
-            .into()
+        let country = self
+            .market_or_default(country.into())?
             .unwrap_or(Country::UnitedStates)
             .as_str()
             .to_string();
@@ -388,6 +1170,54 @@ impl Spotify {
         self.convert_result::<FullArtists>(&result)
     }
 
+    ///Read an artist, their top tracks, the first page of their albums and
+    ///their related artists one after another, for an artist page. Only a
+    ///failure to read the artist is an error: if any of the others can't be
+    ///read, it's left empty in the page.
+    ///Parameters:
+    ///- artist_id - the artist ID, URI or URL
+    ///- country - limit the top tracks and albums to one particular country
+    pub fn artist_page(
+        &self,
+        artist_id: &str,
+        country: Option<Country>,
+    ) -> Result<ArtistPage, failure::Error> {
+        let artist = self.artist(artist_id)?;
+        let top_tracks = self.artist_top_tracks(artist_id, country);
+        let albums = self.artist_albums(artist_id, None, country, Some(20), None);
+        let related_artists = self.artist_related_artists(artist_id);
+        let top_tracks = match top_tracks {
+            Ok(payload) => Some(payload.tracks),
+            Err(e) => {
+                error!("couldn't read the top tracks for the artist page: {}", e);
+                None
+            }
+        };
+        let albums = match albums {
+            Ok(albums) => Some(albums),
+            Err(e) => {
+                error!("couldn't read the albums for the artist page: {}", e);
+                None
+            }
+        };
+        let related_artists = match related_artists {
+            Ok(payload) => Some(payload.artists),
+            Err(e) => {
+                error!(
+                    "couldn't read the related artists for the artist page: {}",
+                    e
+                );
+                None
+            }
+        };
+        Ok(ArtistPage {
+            artist,
+            top_tracks,
+            albums,
+            related_artists,
+        })
+    }
+
     ///[get album](https://developer.spotify.com/web-api/get-album/)
     ///returns a single album given the album's ID, URIs or URL
     ///Parameters:
@@ -401,18 +1231,95 @@ impl Spotify {
     }
 
     ///[get several albums](https://developer.spotify.com/web-api/get-several-albums/)
-    ///returns a list of albums given the album IDs, URIs, or URLs
+    ///returns a list of albums given the album IDs, URIs, or URLs.
+    ///The albums are requested 20 at a time, and the result has one element
+    ///per given ID, `None` where the ID is invalid.
     ///Parameters:
     ///- albums_ids - a list of  album IDs, URIs or URLs
-    pub fn albums(&self, album_ids: Vec<String>) -> Result<FullAlbums, failure::Error> {
-        let mut ids: Vec<String> = vec![];
-        for album_id in album_ids {
-            ids.push(self.get_id(Type::Album, &album_id));
+    pub fn albums(&self, album_ids: Vec<String>) -> Result<Vec<Option<FullAlbum>>, failure::Error> {
+        let ids: Vec<String> = album_ids
+            .iter()
+            .map(|album_id| self.get_id(Type::Album, album_id))
+            .collect();
+        let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+        self.batch_get("albums", &ids, 20, &HashMap::new())
+    }
+
+    ///Fetch several objects of any type from a batch endpoint such as
+    ///`tracks` or `shows`, which takes an `ids` parameter and answers with
+    ///a single list of objects under one key. The IDs are sent `chunk_size`
+    ///at a time and the result has one element per ID, in order, `None`
+    ///where Spotify returned `null`.
+    ///Parameters:
+    ///- base_path - the endpoint, relative to the API prefix
+    ///- ids - the Spotify IDs of the objects
+    ///- chunk_size - the maximum number of IDs the endpoint accepts
+    ///- params - the other query parameters, e.g. `market`
+    pub fn batch_get<T: DeserializeOwned>(
+        &self,
+        base_path: &str,
+        ids: &[&str],
+        chunk_size: usize,
+        params: &HashMap<String, String>,
+    ) -> Result<Vec<Option<T>>, failure::Error> {
+        self.get_in_chunks(base_path, ids, chunk_size, params, |result| {
+            let payload = self.convert_result::<HashMap<String, Vec<Option<T>>>>(result)?;
+            if payload.len() != 1 {
+                bail!(
+                    "expected a single list of objects in response: [{:?}]",
+                    result
+                );
+            }
+            Ok(payload.into_values().flatten().collect())
+        })
+    }
+
+    ///Ask a `contains`-style endpoint about `ids`, `chunk_size` at a time,
+    ///and reassemble the answers into one list in the order of `ids`.
+    fn check_contains(
+        &self,
+        path: &str,
+        ids: &[String],
+        chunk_size: usize,
+        params: &HashMap<String, String>,
+    ) -> Result<Vec<bool>, failure::Error> {
+        let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+        self.get_in_chunks(path, &ids, chunk_size, params, |result| {
+            self.convert_result::<Vec<bool>>(result)
+        })
+    }
+
+    ///Send `ids` to the endpoint `path` `chunk_size` at a time, as its `ids`
+    ///parameter, and concatenate the answers `parse` reads from every
+    ///response. Each response must have one answer per ID, so that the
+    ///answers are in the order of `ids`.
+    fn get_in_chunks<T, F: Fn(&str) -> Result<Vec<T>, failure::Error>>(
+        &self,
+        path: &str,
+        ids: &[&str],
+        chunk_size: usize,
+        params: &HashMap<String, String>,
+        parse: F,
+    ) -> Result<Vec<T>, failure::Error> {
+        if chunk_size == 0 {
+            bail!("the chunk size of a batch request must be positive");
         }
-        let url = format!("albums/?ids={}", ids.join(","));
-        // url.push_str(&ids.join(","));
-        let result = self.get(&url, &mut HashMap::new())?;
-        self.convert_result::<FullAlbums>(&result)
+        let mut answers = Vec::with_capacity(ids.len());
+        for chunk in ids.chunks(chunk_size) {
+            let mut params = params.clone();
+            params.insert("ids".to_owned(), chunk.join(","));
+            let result = self.get(path, &mut params)?;
+            let chunk_answers = parse(&result)?;
+            if chunk_answers.len() != chunk.len() {
+                bail!(
+                    "expected {} answers, got {}",
+                    chunk.len(),
+                    chunk_answers.len()
+                );
+            }
+            answers.extend(chunk_answers);
+        }
+        Ok(answers)
     }
 
     ///[search for items](https://developer.spotify.com/web-api/search-item/)
@@ -420,25 +1327,31 @@ impl Spotify {
     ///Get Spotify catalog information about artists, albums, tracks or
     /// playlists that match a keyword string.
     ///            Parameters:
-    ///- q - the search query
+    ///- q - the search query, which can't be blank
     ///- limit  - the number of items to return
     ///- offset - the index of the first item to return
     ///- type - the type of item to return. One of 'artist', 'album',
     ///'track' or 'playlist'
     ///- market - An ISO 3166-1 alpha-2 country code or the string from_token.
-    pub fn search<L: Into<Option<u32>>, O: Into<Option<u32>>>(
+    pub fn search<Q: Into<String>, L: Into<Option<u32>>, O: Into<Option<u32>>>(
         &self,
-        q: &str,
+        q: Q,
         _type: SearchType,
         limit: L,
         offset: O,
         market: Option<Country>,
         include_external: Option<IncludeExternal>,
     ) -> Result<SearchResult, failure::Error> {
+        let q = q.into();
+        if q.trim().is_empty() {
+            return Err(
+                ClientError::InvalidParameter("the search query is empty".to_owned()).into(),
+            );
+        }
         let mut params = HashMap::new();
         let limit = limit.into().unwrap_or(10);
         let offset = offset.into().unwrap_or(0);
-        if let Some(_market) = market {
+        if let Some(_market) = self.market_or_default(market)? {
             params.insert("market".to_owned(), _market.as_str().to_owned());
         }
         if let Some(_include_external) = include_external {
@@ -449,13 +1362,151 @@ impl Spotify {
         }
         params.insert("limit".to_owned(), limit.to_string());
         params.insert("offset".to_owned(), offset.to_string());
-        params.insert("q".to_owned(), q.to_owned());
+        params.insert("q".to_owned(), q);
         params.insert("type".to_owned(), _type.as_str().to_owned());
         let url = String::from("search");
         let result = self.get(&url, &mut params)?;
         self.convert_result::<SearchResult>(&result)
     }
 
+    ///[search for items](https://developer.spotify.com/web-api/search-item/)
+    ///Search for playlists, getting their page without matching on the
+    ///`SearchResult`.
+    ///Parameters: see `search`
+    pub fn search_playlists<Q: Into<String>, L: Into<Option<u32>>, O: Into<Option<u32>>>(
+        &self,
+        q: Q,
+        limit: L,
+        offset: O,
+        market: Option<Country>,
+    ) -> Result<Page<SimplifiedPlaylist>, failure::Error> {
+        self.search(q, SearchType::Playlist, limit, offset, market, None)?
+            .into_playlists()
+    }
+
+    ///[search for items](https://developer.spotify.com/web-api/search-item/)
+    ///Search for albums, getting their page without matching on the
+    ///`SearchResult`.
+    ///Parameters: see `search`
+    pub fn search_albums<Q: Into<String>, L: Into<Option<u32>>, O: Into<Option<u32>>>(
+        &self,
+        q: Q,
+        limit: L,
+        offset: O,
+        market: Option<Country>,
+    ) -> Result<Page<SimplifiedAlbum>, failure::Error> {
+        self.search(q, SearchType::Album, limit, offset, market, None)?
+            .into_albums()
+    }
+
+    ///[search for items](https://developer.spotify.com/web-api/search-item/)
+    ///Search for artists, getting their page without matching on the
+    ///`SearchResult`.
+    ///Parameters: see `search`
+    pub fn search_artists<Q: Into<String>, L: Into<Option<u32>>, O: Into<Option<u32>>>(
+        &self,
+        q: Q,
+        limit: L,
+        offset: O,
+        market: Option<Country>,
+    ) -> Result<Page<FullArtist>, failure::Error> {
+        self.search(q, SearchType::Artist, limit, offset, market, None)?
+            .into_artists()
+    }
+
+    ///[search for items](https://developer.spotify.com/web-api/search-item/)
+    ///Search for tracks, getting their page without matching on the
+    ///`SearchResult`.
+    ///Parameters: see `search`
+    pub fn search_tracks<Q: Into<String>, L: Into<Option<u32>>, O: Into<Option<u32>>>(
+        &self,
+        q: Q,
+        limit: L,
+        offset: O,
+        market: Option<Country>,
+    ) -> Result<Page<FullTrack>, failure::Error> {
+        self.search(q, SearchType::Track, limit, offset, market, None)?
+            .into_tracks()
+    }
+
+    ///[search for items](https://developer.spotify.com/web-api/search-item/)
+    ///Search for shows, getting their page without matching on the
+    ///`SearchResult`.
+    ///Parameters: see `search`
+    pub fn search_shows<Q: Into<String>, L: Into<Option<u32>>, O: Into<Option<u32>>>(
+        &self,
+        q: Q,
+        limit: L,
+        offset: O,
+        market: Option<Country>,
+        include_external: Option<IncludeExternal>,
+    ) -> Result<Page<SimplifiedShow>, failure::Error> {
+        self.search(q, SearchType::Show, limit, offset, market, include_external)?
+            .into_shows()
+    }
+
+    ///[search for items](https://developer.spotify.com/web-api/search-item/)
+    ///Search for episodes, getting their page without matching on the
+    ///`SearchResult`.
+    ///Parameters: see `search`
+    pub fn search_episodes<Q: Into<String>, L: Into<Option<u32>>, O: Into<Option<u32>>>(
+        &self,
+        q: Q,
+        limit: L,
+        offset: O,
+        market: Option<Country>,
+        include_external: Option<IncludeExternal>,
+    ) -> Result<Page<SimplifiedEpisode>, failure::Error> {
+        self.search(
+            q,
+            SearchType::Episode,
+            limit,
+            offset,
+            market,
+            include_external,
+        )?
+        .into_episodes()
+    }
+
+    ///[search for items](https://developer.spotify.com/web-api/search-item/)
+    ///Get every result of a search, requesting pages of 50. Spotify doesn't
+    ///page search results past an offset of 1000 (limit included), even when
+    ///the `total` is higher, so paging stops there, or at the first empty
+    ///page, and `truncated` tells whether results were left out.
+    ///Parameters: see `search`
+    pub fn search_all<Q: Into<String>>(
+        &self,
+        q: Q,
+        _type: SearchType,
+        market: Option<Country>,
+        include_external: Option<IncludeExternal>,
+    ) -> Result<SearchAll, failure::Error> {
+        let q = q.into();
+        let mut offset = 0;
+        let mut all: Option<SearchResult> = None;
+        loop {
+            let limit = cmp::min(50, SEARCH_MAX_OFFSET - offset);
+            let page = self.search(q.as_str(), _type, limit, offset, market, include_external)?;
+            let len = page.len() as u32;
+            let has_next = page.has_next();
+            let result = match all {
+                Some(mut result) => {
+                    result.append(page)?;
+                    result
+                }
+                None => page,
+            };
+            offset += len;
+            if len == 0 || !has_next || offset >= SEARCH_MAX_OFFSET {
+                return Ok(SearchAll {
+                    truncated: offset < result.total(),
+                    result,
+                });
+            }
+            all = Some(result);
+        }
+    }
+
     ///[get albums tracks](https://developer.spotify.com/web-api/get-albums-tracks/)
     ///Get Spotify catalog information about an album's tracks
     ///Parameters:
@@ -479,6 +1530,57 @@ impl Spotify {
         self.convert_result::<Page<SimplifiedTrack>>(&result)
     }
 
+    ///[get albums tracks](https://developer.spotify.com/web-api/get-albums-tracks/)
+    ///Get all the tracks of an album, requesting pages of 50 until there is
+    ///no next page. The market is sent with every page, so relinked tracks
+    ///keep their `is_playable` and `linked_from` fields.
+    ///Parameters:
+    ///- album_id - the album ID, URI or URL
+    ///- market - an ISO 3166-1 alpha-2 country code, to apply track relinking
+    pub fn album_tracks_all(
+        &self,
+        album_id: &str,
+        market: Option<Country>,
+    ) -> Result<Vec<SimplifiedTrack>, failure::Error> {
+        let trid = self.get_id(Type::Album, album_id);
+        let url = format!("albums/{}/tracks", trid);
+        let market = self.market_or_default(market)?;
+        fetch_all_pages(|offset| {
+            let mut params = HashMap::new();
+            params.insert("limit".to_owned(), 50.to_string());
+            params.insert("offset".to_owned(), offset.to_string());
+            if let Some(_market) = market {
+                params.insert("market".to_owned(), _market.as_str().to_owned());
+            }
+            let result = self.get(&url, &mut params)?;
+            self.convert_result::<Page<SimplifiedTrack>>(&result)
+        })
+        .collect()
+    }
+
+    ///[get albums tracks](https://developer.spotify.com/web-api/get-albums-tracks/)
+    ///Get all the tracks of an album as full tracks, which have the
+    ///popularity, external IDs such as the ISRC and the full album that
+    ///simplified tracks lack. This costs more requests than
+    ///`album_tracks_all`, which it builds on, as the full tracks are then
+    ///requested 50 at a time with `tracks`.
+    ///Parameters:
+    ///- album_id - the album ID, URI or URL
+    ///- market - an ISO 3166-1 alpha-2 country code, to apply track relinking
+    pub fn album_tracks_full(
+        &self,
+        album_id: &str,
+        market: Option<Country>,
+    ) -> Result<Vec<FullTrack>, failure::Error> {
+        let tracks = self.album_tracks_all(album_id, market)?;
+        let ids: Vec<&str> = tracks
+            .iter()
+            .filter_map(|track| track.id.as_deref())
+            .collect();
+        // the IDs come from the album, none of them is invalid
+        Ok(self.tracks(ids, market)?.into_iter().flatten().collect())
+    }
+
     ///[get users profile](https://developer.spotify.com/web-api/get-users-profile/)
     ///Gets basic profile information about a Spotify User
     ///Parameters:
@@ -493,6 +1595,7 @@ impl Spotify {
     ///Get full details about Spotify playlist
     ///Parameters:
     ///- playlist_id - the id of the playlist
+    ///- fields - which fields to return, see `util::FieldsBuilder`
     ///- market - an ISO 3166-1 alpha-2 country code.
     pub fn playlist(
         &self,
@@ -504,7 +1607,7 @@ impl Spotify {
         if let Some(_fields) = fields {
             params.insert("fields".to_owned(), _fields.to_string());
         }
-        if let Some(_market) = market {
+        if let Some(_market) = self.market_or_default(market)? {
             params.insert("market".to_owned(), _market.as_str().to_owned());
         }
 
@@ -515,7 +1618,9 @@ impl Spotify {
     }
 
     ///[get users playlists](https://developer.spotify.com/web-api/get-a-list-of-current-users-playlists/)
-    ///Get current user playlists without required getting his profile
+    ///Get current user playlists without required getting his profile.
+    ///These are the playlists the user owns and also the ones they follow,
+    ///see `current_user_owned_playlists_all` for only the former.
     ///Parameters:
     ///- limit  - the number of items to return
     ///- offset - the index of the first item to return
@@ -533,6 +1638,26 @@ impl Spotify {
         self.convert_result::<Page<SimplifiedPlaylist>>(&result)
     }
 
+    ///[get users playlists](https://developer.spotify.com/web-api/get-a-list-of-current-users-playlists/)
+    ///Get all the playlists of the current user, owned and followed ones,
+    ///requesting pages of 50 until there is no next page.
+    pub fn current_user_playlists_all(&self) -> Result<Vec<SimplifiedPlaylist>, failure::Error> {
+        fetch_all_pages(|offset| self.current_user_playlists(50, offset)).collect()
+    }
+
+    ///Get all the playlists the current user owns, leaving out the ones
+    ///they only follow, built on top of `current_user_playlists_all` and
+    ///`current_user_id`. Collaborative playlists of others are left out too,
+    ///even though their tracks can be changed.
+    pub fn current_user_owned_playlists_all(
+        &self,
+    ) -> Result<Vec<SimplifiedPlaylist>, failure::Error> {
+        let user_id = self.current_user_id()?;
+        let mut playlists = self.current_user_playlists_all()?;
+        playlists.retain(|playlist| playlist.owner.id == user_id);
+        Ok(playlists)
+    }
+
     ///[get list users playlists](https://developer.spotify.com/web-api/get-list-users-playlists/)
     ///Gets playlists of a user
     ///Parameters:
@@ -570,7 +1695,7 @@ impl Spotify {
         if let Some(_fields) = fields {
             params.insert("fields".to_owned(), _fields.to_string());
         }
-        if let Some(_market) = market {
+        if let Some(_market) = self.market_or_default(market)? {
             params.insert("market".to_owned(), _market.as_str().to_owned());
         }
         match playlist_id {
@@ -588,18 +1713,41 @@ impl Spotify {
         }
     }
 
-    ///[get playlists tracks](https://developer.spotify.com/web-api/get-playlists-tracks/)
-    ///Get full details of the tracks of a playlist owned by a user
+    ///[get playlists tracks](https://developer.spotify.com/web-api/get-playlists-tracks/)
+    ///Get full details of the tracks of a playlist owned by a user, see
+    ///`playlist_tracks`: Spotify no longer needs `user_id`.
+    ///Parameters:
+    ///- user_id - the id of the user
+    ///- playlist_id - the id of the playlist
+    ///- fields - which fields to return, see `util::FieldsBuilder`
+    ///- limit - the maximum number of tracks to return
+    ///- offset - the index of the first track to return
+    ///- market - an ISO 3166-1 alpha-2 country code.
+    pub fn user_playlist_tracks<L: Into<Option<u32>>, O: Into<Option<u32>>>(
+        &self,
+        _user_id: &str,
+        playlist_id: &str,
+        fields: Option<&str>,
+        limit: L,
+        offset: O,
+        market: Option<Country>,
+    ) -> Result<Page<PlaylistTrack>, failure::Error> {
+        self.playlist_tracks(playlist_id, fields, limit, offset, market)
+    }
+
+    ///[get playlists tracks](https://developer.spotify.com/documentation/web-api/reference/playlists/get-playlists-tracks/)
+    ///Get full details of the tracks of a playlist, without requiring its
+    ///owner. When `market` is given Spotify applies Track Relinking, so each
+    ///track carries `is_playable` and, if it was relinked, `linked_from`
+    ///pointing at the originally requested track.
     ///Parameters:
-    ///- user_id - the id of the user
     ///- playlist_id - the id of the playlist
-    ///- fields - which fields to return
+    ///- fields - which fields to return, see `util::FieldsBuilder`
     ///- limit - the maximum number of tracks to return
     ///- offset - the index of the first track to return
     ///- market - an ISO 3166-1 alpha-2 country code.
-    pub fn user_playlist_tracks<L: Into<Option<u32>>, O: Into<Option<u32>>>(
+    pub fn playlist_tracks<L: Into<Option<u32>>, O: Into<Option<u32>>>(
         &self,
-        user_id: &str,
         playlist_id: &str,
         fields: Option<&str>,
         limit: L,
@@ -609,18 +1757,195 @@ impl Spotify {
         let mut params = HashMap::new();
         params.insert("limit".to_owned(), limit.into().unwrap_or(50).to_string());
         params.insert("offset".to_owned(), offset.into().unwrap_or(0).to_string());
-        if let Some(_market) = market {
+        if let Some(_market) = self.market_or_default(market)? {
             params.insert("market".to_owned(), _market.as_str().to_owned());
         }
         if let Some(_fields) = fields {
             params.insert("fields".to_owned(), _fields.to_string());
         }
         let plid = self.get_id(Type::Playlist, playlist_id);
-        let url = format!("users/{}/playlists/{}/tracks", user_id, plid);
+        let url = format!("playlists/{}/tracks", plid);
         let result = self.get(&url, &mut params)?;
         self.convert_result::<Page<PlaylistTrack>>(&result)
     }
 
+    ///[get playlists tracks](https://developer.spotify.com/documentation/web-api/reference/playlists/get-playlists-tracks/)
+    ///Get all the tracks of a playlist, following the `next` link of each
+    ///page. The items are deserialized as `T`: `PlaylistTrack` without
+    ///`fields`, or a type matching the selection otherwise.
+    ///Parameters:
+    ///- playlist_id - the id of the playlist
+    ///- fields - which fields to return, see `util::FieldsBuilder`. The
+    ///  `items` and `next` fields are added when missing, as paging needs
+    ///  them.
+    ///- market - an ISO 3166-1 alpha-2 country code.
+    ///
+    ///Every page is requested with the same `fields` and `market`: when
+    ///the `next` link leaves them out, they're added back.
+    pub fn playlist_tracks_all<T: DeserializeOwned>(
+        &self,
+        playlist_id: &str,
+        fields: Option<&str>,
+        market: Option<Country>,
+    ) -> Result<Vec<T>, failure::Error> {
+        let mut items = Vec::new();
+        self.for_each_playlist_track(playlist_id, fields, market, |item| {
+            items.push(item);
+            ControlFlow::Continue(())
+        })?;
+        Ok(items)
+    }
+
+    ///[get playlists tracks](https://developer.spotify.com/documentation/web-api/reference/playlists/get-playlists-tracks/)
+    ///Call `f` on every track of a playlist, in order, requesting the pages
+    ///as in `playlist_tracks_all`, but without holding more than one page at
+    ///a time, e.g. to export a huge playlist. `f` returns
+    ///`ControlFlow::Break` to stop before the last track, in which case no
+    ///more pages are requested.
+    ///Parameters:
+    ///- playlist_id - the id of the playlist
+    ///- fields - which fields to return, see `playlist_tracks_all`
+    ///- market - an ISO 3166-1 alpha-2 country code.
+    ///- f - called on every track
+    pub fn for_each_playlist_track<T: DeserializeOwned, F: FnMut(T) -> ControlFlow<()>>(
+        &self,
+        playlist_id: &str,
+        fields: Option<&str>,
+        market: Option<Country>,
+        mut f: F,
+    ) -> Result<(), failure::Error> {
+        let mut carried = Vec::new();
+        if let Some(_fields) = fields {
+            carried.push(("fields", fields_with(_fields, &["items", "next"])));
+        }
+        if let Some(_market) = self.market_or_default(market)? {
+            carried.push(("market", _market.as_str().to_owned()));
+        }
+        let carried: Vec<(&str, &str)> = carried
+            .iter()
+            .map(|(key, value)| (*key, value.as_str()))
+            .collect();
+        let mut params: HashMap<String, String> = carried
+            .iter()
+            .map(|(key, value)| ((*key).to_owned(), (*value).to_owned()))
+            .collect();
+        params.insert("limit".to_owned(), 100.to_string());
+        let plid = self.get_id(Type::Playlist, playlist_id);
+        let mut url = format!("playlists/{}/tracks", plid);
+        loop {
+            let result = self.get(&url, &mut params)?;
+            let mut page = self.convert_result::<Value>(&result)?;
+            let page_items: Vec<T> = serde_json::from_value(page["items"].take())?;
+            for item in page_items {
+                if let ControlFlow::Break(()) = f(item) {
+                    return Ok(());
+                }
+            }
+            match page["next"].as_str() {
+                Some(next) => {
+                    url = with_missing_params(next, &carried)?;
+                    params.clear();
+                }
+                None => return Ok(()),
+            }
+        }
+    }
+
+    ///[get playlists tracks](https://developer.spotify.com/documentation/web-api/reference/playlists/get-playlists-tracks/)
+    ///Write every track of a playlist to `writer` as newline-delimited
+    ///JSON, a `PlaylistTrack` per line, requesting pages of 100 and holding
+    ///a single one at a time, e.g. to back a library up to a file. Tells how
+    ///many tracks were written, see `util::export_ndjson`.
+    ///Parameters:
+    ///- playlist_id - the id of the playlist
+    ///- market - an ISO 3166-1 alpha-2 country code.
+    ///- writer - where the lines are written, which isn't flushed
+    pub fn export_playlist_tracks_ndjson<W: Write>(
+        &self,
+        playlist_id: &str,
+        market: Option<Country>,
+        writer: &mut W,
+    ) -> Result<usize, failure::Error> {
+        let tracks =
+            fetch_all_pages(|offset| self.playlist_tracks(playlist_id, None, 100, offset, market));
+        export_ndjson(tracks, writer)
+    }
+
+    ///[get playlists tracks](https://developer.spotify.com/documentation/web-api/reference/playlists/get-playlists-tracks/)
+    ///Get the number of tracks in a playlist without fetching them, by only
+    ///requesting the `total` field.
+    ///Parameters:
+    ///- playlist_id - the id of the playlist
+    pub fn playlist_track_count(&self, playlist_id: &str) -> Result<u32, failure::Error> {
+        let mut params = HashMap::new();
+        params.insert("fields".to_owned(), "total".to_owned());
+        params.insert("limit".to_owned(), "1".to_owned());
+        let plid = self.get_id(Type::Playlist, playlist_id);
+        let url = format!("playlists/{}/tracks", plid);
+        let result = self.get(&url, &mut params)?;
+        let total = self.convert_result::<Value>(&result)?;
+        total["total"]
+            .as_u64()
+            .map(|total| total as u32)
+            .ok_or_else(|| format_err!("missing total in response: [{:?}]", result))
+    }
+
+    ///[get playlist](https://developer.spotify.com/documentation/web-api/reference/playlists/get-playlist/)
+    ///Get the snapshot id of a playlist, i.e. its current version, by only
+    ///requesting the `snapshot_id` field.
+    ///Parameters:
+    ///- playlist_id - the id of the playlist
+    pub fn playlist_snapshot_id(&self, playlist_id: &str) -> Result<String, failure::Error> {
+        let mut params = HashMap::new();
+        params.insert("fields".to_owned(), "snapshot_id".to_owned());
+        let plid = self.get_id(Type::Playlist, playlist_id);
+        let url = format!("playlists/{}", plid);
+        let result = self.get(&url, &mut params)?;
+        let playlist = self.convert_result::<Value>(&result)?;
+        playlist["snapshot_id"]
+            .as_str()
+            .map(|snapshot_id| snapshot_id.to_owned())
+            .ok_or_else(|| format_err!("missing snapshot_id in response: [{:?}]", result))
+    }
+
+    ///Whether a playlist changed since the version `snapshot_id` was taken
+    ///from, e.g. the `snapshot_id` of a `PlaylistTracksSnapshot`.
+    ///Parameters:
+    ///- playlist_id - the id of the playlist
+    ///- snapshot_id - the snapshot id of the known version
+    pub fn playlist_changed_since(
+        &self,
+        playlist_id: &str,
+        snapshot_id: &str,
+    ) -> Result<bool, failure::Error> {
+        Ok(self.playlist_snapshot_id(playlist_id)? != snapshot_id)
+    }
+
+    ///Get all the tracks of a playlist together with the snapshot id they
+    ///were read at, to keep a copy of the playlist in sync, see
+    ///`PlaylistTracksSnapshot`.
+    ///Parameters:
+    ///- playlist_id - the id of the playlist
+    ///- market - an ISO 3166-1 alpha-2 country code.
+    pub fn playlist_tracks_snapshot(
+        &self,
+        playlist_id: &str,
+        market: Option<Country>,
+    ) -> Result<PlaylistTracksSnapshot, failure::Error> {
+        let playlist = self.playlist(playlist_id, None, market)?;
+        // the first page comes with the playlist
+        let mut first_page = Some(playlist.tracks);
+        let tracks = fetch_all_pages(|offset| match first_page.take() {
+            Some(page) => Ok(page),
+            None => self.playlist_tracks(playlist_id, None, 100, offset, market),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+        Ok(PlaylistTracksSnapshot {
+            snapshot_id: playlist.snapshot_id,
+            tracks,
+        })
+    }
+
     ///[create playlist](https://developer.spotify.com/web-api/create-playlist/)
     ///Creates a playlist for a user
     ///Parameters:
@@ -628,6 +1953,10 @@ impl Spotify {
     ///- name - the name of the playlist
     ///- public - is the created playlist public
     ///- description - the description of the playlist
+    ///
+    ///A blank name, or a name or description above `PLAYLIST_NAME_MAX_LEN`
+    ///or `PLAYLIST_DESCRIPTION_MAX_LEN` characters, is rejected with
+    ///`ClientError::InvalidParameter`.
     pub fn user_playlist_create<P: Into<Option<bool>>, D: Into<Option<String>>>(
         &self,
         user_id: &str,
@@ -637,6 +1966,7 @@ impl Spotify {
     ) -> Result<FullPlaylist, failure::Error> {
         let public = public.into().unwrap_or(true);
         let description = description.into().unwrap_or_else(|| "".to_owned());
+        check_playlist_details(Some(name), Some(&description))?;
         let params = json!({
             "name": name,
             "public": public,
@@ -656,6 +1986,12 @@ impl Spotify {
     ///- public - optional is the playlist public
     ///- collaborative - optional is the playlist collaborative
     ///- description - optional description of the playlist
+    ///
+    ///Collaborative playlists must be private, so `public` and
+    ///`collaborative` both set to true is rejected with
+    ///`ClientError::InvalidParameter`. See `user_playlist_change_collaborative`
+    ///to also check the playlist's ownership. The name and description are checked as
+    ///in `user_playlist_create`.
     pub fn user_playlist_change_detail(
         &self,
         user_id: &str,
@@ -665,6 +2001,8 @@ impl Spotify {
         description: Option<String>,
         collaborative: Option<bool>,
     ) -> Result<String, failure::Error> {
+        check_collaborative_visibility(public, collaborative)?;
+        check_playlist_details(name, description.as_deref())?;
         let mut params = Map::new();
         if let Some(_name) = name {
             params.insert("name".to_owned(), _name.into());
@@ -682,6 +2020,45 @@ impl Spotify {
         self.put(&url, &Value::Object(params))
     }
 
+    ///Makes a playlist collaborative or not, following Spotify's rules for
+    ///collaborative playlists: only the owner of a playlist can make it
+    ///collaborative, and collaborative playlists must be private. Making a
+    ///playlist collaborative thus also makes it private.
+    ///Parameters:
+    ///- user_id - the id of the user
+    ///- playlist_id - the id of the playlist
+    ///- collaborative - whether the playlist should be collaborative
+    ///- check_owner - whether to look the playlist owner up first, failing
+    ///  with `ClientError::NotPlaylistOwner` when it isn't the current user
+    pub fn user_playlist_change_collaborative(
+        &self,
+        user_id: &str,
+        playlist_id: &str,
+        collaborative: bool,
+        check_owner: bool,
+    ) -> Result<String, failure::Error> {
+        let plid = self.get_id(Type::Playlist, playlist_id);
+        if check_owner {
+            let mut params = HashMap::new();
+            params.insert("fields".to_owned(), "owner.id".to_owned());
+            let url = format!("playlists/{}", plid);
+            let result = self.get(&url, &mut params)?;
+            let playlist = self.convert_result::<Value>(&result)?;
+            let owner_id = playlist["owner"]["id"]
+                .as_str()
+                .ok_or_else(|| format_err!("missing owner id in response: [{:?}]", result))?;
+            if owner_id != self.current_user_id()? {
+                return Err(ClientError::NotPlaylistOwner {
+                    playlist_id: plid,
+                    owner_id: owner_id.to_owned(),
+                }
+                .into());
+            }
+        }
+        let public = if collaborative { Some(false) } else { None };
+        self.user_playlist_change_detail(user_id, &plid, None, public, None, Some(collaborative))
+    }
+
     ///[unfollow playlist](https://developer.spotify.com/web-api/unfollow-playlist/)
     ///Unfollows (deletes) a playlist for a user
     ///Parameters:
@@ -703,6 +2080,12 @@ impl Spotify {
     ///- playlist_id - the id of the playlist
     ///- track_ids - a list of track URIs, URLs or IDs
     ///- position - the position to add the tracks
+    ///
+    ///The tracks are added 100 at a time (the endpoint's limit), in order, and
+    ///the snapshot id of the last request is returned. An empty `track_ids`
+    ///fails with `ClientError::InvalidParameter` without sending a request.
+    ///The requests aren't atomic: when one fails, the tracks of the previous
+    ///ones stay in the playlist, and their number is logged as a warning.
     pub fn user_playlist_add_tracks(
         &self,
         user_id: &str,
@@ -711,18 +2094,41 @@ impl Spotify {
         position: Option<i32>,
     ) -> Result<CUDResult, failure::Error> {
         let plid = self.get_id(Type::Playlist, playlist_id);
-        let uris: Vec<String> = track_ids
-            .iter()
-            .map(|id| self.get_uri(Type::Track, id))
-            .collect();
-        let mut params = Map::new();
-        if let Some(_position) = position {
-            params.insert("position".to_owned(), _position.into());
-        }
-        params.insert("uris".to_owned(), uris.into());
         let url = format!("users/{}/playlists/{}/tracks", user_id, plid);
-        let result = self.post(&url, &Value::Object(params))?;
-        self.convert_result::<CUDResult>(&result)
+        let mut position = position;
+        let mut cud_result = None;
+        let mut added = 0;
+        for chunk in track_ids.chunks(100) {
+            let uris: Vec<String> = chunk
+                .iter()
+                .map(|id| self.get_uri(Type::Track, id))
+                .collect();
+            let mut params = Map::new();
+            if let Some(_position) = position {
+                params.insert("position".to_owned(), _position.into());
+                position = Some(_position + chunk.len() as i32);
+            }
+            params.insert("uris".to_owned(), uris.into());
+            let result = self
+                .post(&url, &Value::Object(params))
+                .and_then(|result| self.convert_result::<CUDResult>(&result));
+            match result {
+                Ok(result) => cud_result = Some(result),
+                Err(e) => {
+                    if added > 0 {
+                        warn!(
+                            "{} tracks were added to playlist {} before the failure",
+                            added, plid
+                        );
+                    }
+                    return Err(e);
+                }
+            }
+            added += chunk.len();
+        }
+        cud_result.ok_or_else(|| {
+            ClientError::InvalidParameter(format!("no tracks to add to playlist {}", plid)).into()
+        })
     }
     ///[replaced playlists tracks](https://developer.spotify.com/web-api/replace-playlists-tracks/)
     ///Replace all tracks in a playlist
@@ -773,6 +2179,8 @@ impl Spotify {
         let plid = self.get_id(Type::Playlist, playlist_id);
         let range_length = range_length.into().unwrap_or(1);
         let mut params = Map::new();
+        // without a snapshot a repeated reorder moves the range again
+        let idempotent = snapshot_id.is_some();
         if let Some(_snapshot_id) = snapshot_id {
             params.insert("snapshot_id".to_owned(), _snapshot_id.into());
         }
@@ -780,10 +2188,46 @@ impl Spotify {
         params.insert("range_length".to_owned(), range_length.into());
         params.insert("insert_before".to_owned(), insert_before.into());
         let url = format!("users/{}/playlists/{}/tracks", user_id, plid);
-        let result = self.put(&url, &Value::Object(params))?;
+        let payload = Value::Object(params);
+        let result = if idempotent {
+            self.put(&url, &payload)?
+        } else {
+            self.put_once(&url, &payload)?
+        };
         self.convert_result::<CUDResult>(&result)
     }
 
+    ///Reorder the tracks of a playlist into the given order, using as many
+    ///[reorder playlists tracks](https://developer.spotify.com/web-api/reorder-playlists-tracks/)
+    ///calls as needed. Each call's snapshot id is passed to the next one, and
+    ///the last snapshot id is returned (`snapshot_id` itself if nothing moved).
+    ///Parameters:
+    ///- user_id - the id of the user
+    ///- playlist_id - the id of the playlist
+    ///- order - the current positions of the tracks in their new order, see `util::reorder_operations`
+    ///- snapshot_id - optional playlist's snapshot ID
+    pub fn user_playlist_reorder_tracks_to(
+        &self,
+        user_id: &str,
+        playlist_id: &str,
+        order: &[usize],
+        snapshot_id: Option<String>,
+    ) -> Result<Option<String>, failure::Error> {
+        let mut snapshot_id = snapshot_id;
+        for operation in reorder_operations(order)? {
+            let result = self.user_playlist_recorder_tracks(
+                user_id,
+                playlist_id,
+                operation.range_start as i32,
+                operation.range_length,
+                operation.insert_before as i32,
+                snapshot_id,
+            )?;
+            snapshot_id = Some(result.snapshot_id);
+        }
+        Ok(snapshot_id)
+    }
+
     ///[remove tracks playlist](https://developer.spotify.com/web-api/remove-tracks-playlist/)
     ///Removes all occurrences of the given tracks from the given playlist
     ///Parameters:
@@ -791,6 +2235,12 @@ impl Spotify {
     ///- playlist_id - the id of the playlist
     ///- track_ids - the list of track ids to add to the playlist
     ///- snapshot_id - optional id of the playlist snapshot
+    ///
+    ///The tracks are removed 100 at a time (the endpoint's limit). Each
+    ///request is made against the snapshot the previous one returned, and the
+    ///snapshot id of the last request is returned. An empty `track_ids`
+    ///fails with `ClientError::InvalidParameter` without sending a request,
+    ///as there'd be no snapshot id to return.
     pub fn user_playlist_remove_all_occurrences_of_tracks(
         &self,
         user_id: &str,
@@ -799,24 +2249,24 @@ impl Spotify {
         snapshot_id: Option<String>,
     ) -> Result<CUDResult, failure::Error> {
         let plid = self.get_id(Type::Playlist, playlist_id);
-        let uris: Vec<String> = track_ids
-            .iter()
-            .map(|id| self.get_uri(Type::Track, id))
-            .collect();
-        let mut params = Map::new();
-        let mut tracks: Vec<Map<String, Value>> = vec![];
-        for uri in uris {
-            let mut map = Map::new();
-            map.insert("uri".to_owned(), uri.into());
-            tracks.push(map);
-        }
-        params.insert("tracks".to_owned(), tracks.into());
-        if let Some(_snapshot_id) = snapshot_id {
-            params.insert("snapshot_id".to_owned(), _snapshot_id.into());
-        }
         let url = format!("users/{}/playlists/{}/tracks", user_id, plid);
-        let result = self.delete(&url, &Value::Object(params))?;
-        self.convert_result::<CUDResult>(&result)
+        let mut snapshot_id = snapshot_id;
+        let mut cud_result = None;
+        for chunk in track_ids.chunks(100) {
+            let uris: Vec<String> = chunk
+                .iter()
+                .map(|id| self.get_uri(Type::Track, id))
+                .collect();
+            let params = remove_tracks_payload(&uris, snapshot_id.as_deref());
+            let result = self.delete(&url, &params)?;
+            let chunk_result = self.convert_result::<CUDResult>(&result)?;
+            snapshot_id = Some(chunk_result.snapshot_id.clone());
+            cud_result = Some(chunk_result);
+        }
+        cud_result.ok_or_else(|| {
+            ClientError::InvalidParameter(format!("no tracks to remove from playlist {}", plid))
+                .into()
+        })
     }
 
     ///[remove tracks playlist](https://developer.spotify.com/web-api/remove-tracks-playlist/)
@@ -851,11 +2301,16 @@ impl Spotify {
             ftracks.push(map);
         }
         params.insert("tracks".to_owned(), ftracks.into());
-        if let Some(_snapshot_id) = snapshot_id {
-            params.insert("snapshot_id".to_owned(), _snapshot_id.into());
-        }
         let url = format!("users/{}/playlists/{}/tracks", user_id, plid);
-        let result = self.delete(&url, &Value::Object(params))?;
+        // the tracks are removed by position, which a replay after the
+        // playlist changed would get wrong, unless the snapshot pins them
+        let result = match snapshot_id {
+            Some(_snapshot_id) => {
+                params.insert("snapshot_id".to_owned(), _snapshot_id.into());
+                self.delete(&url, &Value::Object(params))?
+            }
+            None => self.delete_once(&url, &Value::Object(params))?,
+        };
         self.convert_result::<CUDResult>(&result)
     }
 
@@ -912,11 +2367,16 @@ impl Spotify {
     ///[get current users profile](https://developer.spotify.com/web-api/get-current-users-profile/)
     ///Get detailed profile information about the current user.
     ///An alias for the 'current_user' method.
+    ///The profile is always requested, and then cached for
+    ///`current_user_cached` and the helpers built on it, such as
+    ///`current_user_id`.
     pub fn me(&self) -> Result<PrivateUser, failure::Error> {
         let mut dumb: HashMap<String, String> = HashMap::new();
         let url = String::from("me/");
         let result = self.get(&url, &mut dumb)?;
-        self.convert_result::<PrivateUser>(&result)
+        let user = self.convert_result::<PrivateUser>(&result)?;
+        *self.user_cache.lock().unwrap() = Some(user.clone());
+        Ok(user)
     }
     ///Get detailed profile information about the current user.
     ///An alias for the 'me' method.
@@ -924,6 +2384,53 @@ impl Spotify {
         self.me()
     }
 
+    ///Get the profile of the current user, requested once and then cached
+    ///until `set_token` changes the user. Clones share the cache, which `me`
+    ///fills too.
+    pub fn current_user_cached(&self) -> Result<PrivateUser, failure::Error> {
+        if let Some(ref user) = *self.user_cache.lock().unwrap() {
+            return Ok(user.clone());
+        }
+        self.me()
+    }
+
+    ///When the current token expires, for refreshing it ahead of time
+    ///rather than when a request needs it. `None` when it isn't known: no
+    ///token was obtained yet, or the token was given with `access_token`.
+    pub fn token_expires_at(&self) -> Option<DateTime<Utc>> {
+        if self.access_token.is_some() {
+            return None;
+        }
+        self.client_credentials_manager
+            .as_ref()
+            .and_then(SpotifyClientCredentials::token_expires_at)
+    }
+
+    ///Get the country of the current user, which is the market their
+    ///content availability is based on, see `current_user_cached`. `None`
+    ///without the `user-read-private` scope.
+    pub fn current_user_market(&self) -> Result<Option<Country>, failure::Error> {
+        Ok(self.current_user_cached()?.market())
+    }
+
+    ///Get the id of the current user, see `current_user_cached`.
+    pub fn current_user_id(&self) -> Result<String, failure::Error> {
+        Ok(self.current_user_cached()?.id)
+    }
+
+    ///Whether the current user owns a playlist, e.g. to decide whether to
+    ///show edit controls, see `current_user_id`. Pass the `owner` of a
+    ///`FullPlaylist` or a `SimplifiedPlaylist`.
+    ///
+    ///The tracks of a collaborative playlist can be changed by anyone it is
+    ///shared with, so check its `collaborative` flag too; its details and
+    ///collaborative state stay reserved to the owner.
+    ///Parameters:
+    ///- owner - the owner of the playlist
+    pub fn playlist_is_owned_by_me(&self, owner: &PublicUser) -> Result<bool, failure::Error> {
+        Ok(owner.id == self.current_user_id()?)
+    }
+
     /// [get the users currently playing track](https://developer.spotify.com/web-api/get-the-users-currently-playing-track/)
     /// Get information about the current users currently playing track.
     pub fn current_user_playing_track(&self) -> Result<Option<Playing>, failure::Error> {
@@ -962,6 +2469,14 @@ impl Spotify {
         let result = self.get(&url, &mut params)?;
         self.convert_result::<Page<SavedAlbum>>(&result)
     }
+
+    ///[get user saved albums](https://developer.spotify.com/web-api/get-users-saved-albums/)
+    ///Get the number of albums saved in the current user's library without
+    ///paging it, e.g. for a profile: a single album is requested, for the
+    ///`total` of its page.
+    pub fn current_user_saved_albums_count(&self) -> Result<u32, failure::Error> {
+        self.saved_count("me/albums")
+    }
     ///[get users saved tracks](https://developer.spotify.com/web-api/get-users-saved-tracks/)
     ///Parameters:
     ///- limit - the number of tracks to return
@@ -981,6 +2496,59 @@ impl Spotify {
         let result = self.get(&url, &mut params)?;
         self.convert_result::<Page<SavedTrack>>(&result)
     }
+
+    ///[get users saved tracks](https://developer.spotify.com/web-api/get-users-saved-tracks/)
+    ///Get the number of tracks saved in the current user's library without
+    ///paging it, e.g. for a profile: a single track is requested, for the
+    ///`total` of its page.
+    pub fn current_user_saved_tracks_count(&self) -> Result<u32, failure::Error> {
+        self.saved_count("me/tracks")
+    }
+    ///[get users saved tracks](https://developer.spotify.com/web-api/get-users-saved-tracks/)
+    ///Write the saved tracks of the current user to `writer` as
+    ///newline-delimited JSON, a `SavedTrack` per line, newest first,
+    ///requesting pages of 50 and holding a single one at a time, e.g. to back
+    ///the library up to a file. Tells how many tracks were written, see
+    ///`util::export_ndjson`.
+    ///Parameters:
+    ///- writer - where the lines are written, which isn't flushed
+    pub fn export_saved_tracks_ndjson<W: Write>(
+        &self,
+        writer: &mut W,
+    ) -> Result<usize, failure::Error> {
+        let tracks = fetch_all_pages(|offset| self.current_user_saved_tracks(50, offset));
+        export_ndjson(tracks, writer)
+    }
+
+    ///[get users saved tracks](https://developer.spotify.com/web-api/get-users-saved-tracks/)
+    ///Get the saved tracks of the current user, newest first, requesting
+    ///pages of 50 until `stop` returns true for a track, so that the rest of
+    ///the library isn't loaded. The track `stop` matched is left out, e.g.
+    ///`|track| track.added_at < date` gets the tracks saved since `date`.
+    ///Parameters:
+    ///- stop - called on every track in order, true to stop paging
+    pub fn current_user_saved_tracks_until<F: FnMut(&SavedTrack) -> bool>(
+        &self,
+        mut stop: F,
+    ) -> Result<Vec<SavedTrack>, failure::Error> {
+        let mut tracks = Vec::new();
+        for track in fetch_all_pages(|offset| self.current_user_saved_tracks(50, offset)) {
+            let track = track?;
+            if stop(&track) {
+                break;
+            }
+            tracks.push(track);
+        }
+        Ok(tracks)
+    }
+
+    ///[get users saved tracks](https://developer.spotify.com/web-api/get-users-saved-tracks/)
+    ///Get all the saved tracks of the current user, newest first, see
+    ///`current_user_saved_tracks_until` to stop early.
+    pub fn current_user_saved_tracks_all(&self) -> Result<Vec<SavedTrack>, failure::Error> {
+        self.current_user_saved_tracks_until(|_| false)
+    }
+
     ///[get followed artists](https://developer.spotify.com/web-api/get-followed-artists/)
     ///Gets a list of the artists followed by the current authorized user
     ///Parameters:
@@ -1003,6 +2571,25 @@ impl Spotify {
         self.convert_result::<CursorPageFullArtists>(&result)
     }
 
+    ///[get followed artists](https://developer.spotify.com/web-api/get-followed-artists/)
+    ///Gets all the artists followed by the current authorized user, following
+    ///the `after` cursor until every page is read. Use
+    ///`current_user_followed_artists` instead to report progress, its
+    ///`total` is the number of followed artists.
+    pub fn current_user_followed_artists_all(&self) -> Result<Vec<FullArtist>, failure::Error> {
+        let mut artists = Vec::new();
+        let mut after = None;
+        loop {
+            let page = self.current_user_followed_artists(50, after)?.artists;
+            artists.extend(page.items);
+            after = page.cursors.and_then(|cursors| cursors.after);
+            if page.next.is_none() || after.is_none() {
+                break;
+            }
+        }
+        Ok(artists)
+    }
+
     ///[remove tracks users](https://developer.spotify.com/web-api/remove-tracks-user/)
     ///Remove one or more tracks from the current user's
     ///"Your Music" library.
@@ -1025,26 +2612,25 @@ impl Spotify {
 
     ///[check users saved tracks](https://developer.spotify.com/web-api/check-users-saved-tracks/)
     ///Check if one or more tracks is already saved in
-    ///the current Spotify user’s “Your Music” library.
+    ///the current Spotify user’s “Your Music” library, 50 per request. The
+    ///answers are in the order of `track_ids`.
     ///Parameters:
     ///- track_ids - a list of track URIs, URLs or IDs
     pub fn current_user_saved_tracks_contains(
         &self,
         track_ids: &[String],
     ) -> Result<Vec<bool>, failure::Error> {
-        let uris: Vec<String> = track_ids
+        let ids: Vec<String> = track_ids
             .iter()
             .map(|id| self.get_id(Type::Track, id))
             .collect();
-        let url = format!("me/tracks/contains/?ids={}", uris.join(","));
-        let mut dumb = HashMap::new();
-        let result = self.get(&url, &mut dumb)?;
-        self.convert_result::<Vec<bool>>(&result)
+        self.check_contains("me/tracks/contains", &ids, 50, &HashMap::new())
     }
 
     ///[save tracks user ](https://developer.spotify.com/web-api/save-tracks-user/)
     ///Save one or more tracks to the current user's
-    ///"Your Music" library.
+    ///"Your Music" library. Saving an already saved track is a no-op, but
+    ///still costs a request, see `set_saved` to skip it.
     ///Parameters:
     ///- track_ids - a list of track URIs, URLs or IDs
     pub fn current_user_saved_tracks_add(
@@ -1062,6 +2648,68 @@ impl Spotify {
         }
     }
 
+    ///[save tracks user ](https://developer.spotify.com/web-api/save-tracks-user/)
+    ///Save tracks to the current user's "Your Music" library, reporting which
+    ///of them were newly saved and which were already saved. Spotify's save
+    ///endpoint doesn't tell, so each batch of 50 tracks is checked with
+    ///`current_user_saved_tracks_contains` first, costing an extra request;
+    ///use `current_user_saved_tracks_add` when the report isn't needed. Only
+    ///the tracks that aren't saved yet are sent to the save endpoint.
+    ///Parameters:
+    ///- track_ids - a list of track URIs, URLs or IDs
+    pub fn current_user_saved_tracks_add_reporting(
+        &self,
+        track_ids: &[String],
+    ) -> Result<AddReport, failure::Error> {
+        let mut report = AddReport::default();
+        for chunk in track_ids.chunks(50) {
+            let ids: Vec<String> = chunk
+                .iter()
+                .map(|id| self.get_id(Type::Track, id))
+                .collect();
+            let contains = self.current_user_saved_tracks_contains(&ids)?;
+            if contains.len() != ids.len() {
+                bail!(
+                    "expected {} saved states, got {}",
+                    ids.len(),
+                    contains.len()
+                );
+            }
+            let (present, missing): (Vec<_>, Vec<_>) =
+                ids.into_iter().zip(contains).partition(|(_, saved)| *saved);
+            let missing: Vec<String> = missing.into_iter().map(|(id, _)| id).collect();
+            if !missing.is_empty() {
+                self.current_user_saved_tracks_add(&missing)?;
+            }
+            report.newly_added.extend(missing);
+            report
+                .already_present
+                .extend(present.into_iter().map(|(id, _)| id));
+        }
+        Ok(report)
+    }
+
+    ///Save or remove a track from the current user's "Your Music" library,
+    ///checking first whether it's already saved so that the write is only
+    ///sent when the state actually changes (e.g. for a "like" toggle).
+    ///Returns whether the library changed.
+    ///Parameters:
+    ///- track_id - a track URI, URL or ID
+    ///- saved - whether the track should end up saved
+    pub fn set_saved(&self, track_id: &str, saved: bool) -> Result<bool, failure::Error> {
+        let track_ids = [track_id.to_owned()];
+        let contains = self.current_user_saved_tracks_contains(&track_ids)?;
+        if contains.first() == Some(&saved) {
+            return Ok(false);
+        }
+        if saved {
+            self.current_user_saved_tracks_add(&track_ids)?;
+        } else {
+            self.current_user_saved_tracks_delete(&track_ids)?;
+        }
+        Ok(true)
+    }
+
     ///[get users  top artists and tracks](https://developer.spotify.com/web-api/get-users-top-artists-and-tracks/)
     ///Get the current user's top artists
     ///Parameters:
@@ -1091,6 +2739,22 @@ impl Spotify {
         self.convert_result::<Page<FullArtist>>(&result)
     }
 
+    ///Get the genres of the current user's top artists, most frequent first.
+    ///Spotify has no top genres endpoint, so they are derived from the
+    ///artists: each of them counts once per genre, unweighted by its rank,
+    ///and ties are ordered by the best ranked artist carrying the genre.
+    ///Parameters:
+    ///- time_range - Over what time frame are the affinities computed
+    ///- limit - the number of top artists to aggregate, 50 at most
+    pub fn top_genres<T: Into<Option<TimeRange>>, L: Into<Option<u32>>>(
+        &self,
+        time_range: T,
+        limit: L,
+    ) -> Result<Vec<(String, usize)>, failure::Error> {
+        let artists = self.current_user_top_artists(limit, None, time_range)?;
+        Ok(rank_genres(&artists.items))
+    }
+
     ///[get users top artists and tracks](https://developer.spotify.com/web-api/get-users-top-artists-and-tracks/)
     ///Get the current user's top tracks
     ///Parameters:
@@ -1123,18 +2787,61 @@ impl Spotify {
     ///Get the current user's recently played tracks
     ///Parameters:
     ///- limit - the number of entities to return
+    ///- time_limits - only return plays before or after a point in time,
+    ///  e.g. `TimeLimits::After` the start of the day for today's plays
     pub fn current_user_recently_played<L: Into<Option<u32>>>(
         &self,
         limit: L,
+        time_limits: Option<TimeLimits>,
     ) -> Result<CursorBasedPage<PlayHistory>, failure::Error> {
         let limit = limit.into().unwrap_or(50);
         let mut params = HashMap::new();
         params.insert("limit".to_owned(), limit.to_string());
+        if let Some(_time_limits) = time_limits {
+            let (param, timestamp) = _time_limits.param();
+            params.insert(param.to_owned(), timestamp);
+        }
         let url = String::from("me/player/recently-played");
         let result = self.get(&url, &mut params)?;
         self.convert_result::<CursorBasedPage<PlayHistory>>(&result)
     }
 
+    ///[get recently played](https://developer.spotify.com/web-api/web-api-personalization-endpoints/get-recently-played/)
+    ///Get the current user's plays of the last `within`, e.g. a week for
+    ///weekly stats, newest first, paging back from now until a play is
+    ///older than that. Spotify only keeps the last 50 or so plays, so the
+    ///history may run out before the window does, and the plays it has are
+    ///returned then.
+    ///Parameters:
+    ///- within - how far back to go
+    pub fn current_user_recently_played_within(
+        &self,
+        within: Duration,
+    ) -> Result<Vec<PlayHistory>, failure::Error> {
+        // a window too large for a date reaches the start of the history
+        let since = chrono::Duration::from_std(within)
+            .ok()
+            .and_then(|within| Utc::now().checked_sub_signed(within));
+        let mut plays = Vec::new();
+        let mut time_limits = None;
+        loop {
+            let page = self.current_user_recently_played(50, time_limits)?;
+            let oldest = page.items.last().map(|play| play.played_at);
+            let (recent, stopped) = take_until(
+                page.items,
+                |play| matches!(since, Some(since) if play.played_at < since),
+            );
+            plays.extend(recent);
+            match oldest {
+                Some(oldest) if !stopped && page.next.is_some() => {
+                    time_limits = Some(TimeLimits::Before(oldest));
+                }
+                _ => break,
+            }
+        }
+        Ok(plays)
+    }
+
     ///[save albums user](https://developer.spotify.com/web-api/save-albums-user/)
     ///Add one or more albums to the current user's
     ///"Your Music" library.
@@ -1218,22 +2925,55 @@ impl Spotify {
         }
     }
 
+    ///[unfollow artists users](https://developer.spotify.com/documentation/web-api/reference/follow/unfollow-artists-users/)
+    ///Unfollow every artist the current user follows, 50 per request, and
+    ///tell how many were unfollowed. The followed artists are all read
+    ///first, as unfollowing them while paging would move the cursor. This
+    ///can't be undone, so it must be confirmed with `Confirm::Yes`.
+    pub fn user_unfollow_all_artists(&self, _confirm: Confirm) -> Result<usize, failure::Error> {
+        let ids: Vec<String> = self
+            .current_user_followed_artists_all()?
+            .into_iter()
+            .map(|artist| artist.id)
+            .collect();
+        for chunk in ids.chunks(50) {
+            self.user_unfollow_artists(chunk)?;
+        }
+        Ok(ids.len())
+    }
+
     ///[check user following
     ///artists](https://developer.spotify.com/web-api/checkcurrent-user-follows/)
-    ///Check to see if the given users are following the given artists
+    ///Check to see if the current user is following the given artists, 50
+    ///per request. The answers are in the order of `artist_ids`.
     ///Parameters:
-    ///- artist_ids - the ids of the users that you want to
+    ///- artist_ids - a list of artist URIs, URLs or IDs
     pub fn user_artist_check_follow(
         &self,
-        artsit_ids: &[String],
+        artist_ids: &[String],
     ) -> Result<Vec<bool>, failure::Error> {
-        let url = format!(
-            "me/following/contains?type=artist&ids={}",
-            artsit_ids.join(",")
-        );
-        let mut dumb = HashMap::new();
-        let result = self.get(&url, &mut dumb)?;
-        self.convert_result::<Vec<bool>>(&result)
+        let ids: Vec<String> = artist_ids
+            .iter()
+            .map(|id| self.get_id(Type::Artist, id))
+            .collect();
+        let mut params = HashMap::new();
+        params.insert("type".to_owned(), "artist".to_owned());
+        self.check_contains("me/following/contains", &ids, 50, &params)
+    }
+
+    ///[check user following
+    ///users](https://developer.spotify.com/documentation/web-api/reference/follow/check-current-user-follows/)
+    ///Check to see if the current user is following the given users, 50
+    ///per request. The answers are in the order of `user_ids`.
+    ///Parameters:
+    ///- user_ids - a list of user IDs
+    pub fn user_check_follow_users(
+        &self,
+        user_ids: &[String],
+    ) -> Result<Vec<bool>, failure::Error> {
+        let mut params = HashMap::new();
+        params.insert("type".to_owned(), "user".to_owned());
+        self.check_contains("me/following/contains", user_ids, 50, &params)
     }
 
     ///[follow artists users](https://developer.spotify.com/web-api/follow-artists-users/)
@@ -1290,7 +3030,7 @@ impl Spotify {
         if let Some(_locale) = locale {
             params.insert("locale".to_owned(), _locale);
         }
-        if let Some(_country) = country {
+        if let Some(_country) = self.market_or_default(country)? {
             params.insert("country".to_owned(), _country.as_str().to_owned());
         }
         if let Some(_timestamp) = timestamp {
@@ -1321,7 +3061,7 @@ impl Spotify {
         let mut params = HashMap::new();
         let limit = limit.into().unwrap_or(20);
         let offset = offset.into().unwrap_or(0);
-        if let Some(_country) = country {
+        if let Some(_country) = self.market_or_default(country)? {
             params.insert("country".to_owned(), _country.as_str().to_owned());
         }
         params.insert("limit".to_owned(), limit.to_string());
@@ -1331,6 +3071,23 @@ impl Spotify {
         self.convert_result::<PageSimpliedAlbums>(&result)
     }
 
+    ///[get list new releases](https://developer.spotify.com/web-api/get-list-new-releases/)
+    ///Get all the new album releases featured in Spotify, requesting pages
+    ///of 50 until there is no next page. Spotify only features a hundred or
+    ///so releases, so this takes a few requests.
+    ///Parameters:
+    ///- country - An ISO 3166-1 alpha-2 country code.
+    pub fn new_releases_all(
+        &self,
+        country: Option<Country>,
+    ) -> Result<Vec<SimplifiedAlbum>, failure::Error> {
+        fetch_all_pages(|offset| {
+            self.new_releases(country, 50, offset)
+                .map(|releases| releases.albums)
+        })
+        .collect()
+    }
+
     ///[get list categories](https://developer.spotify.com/web-api/get-list-categories/)
     ///Get a list of new album releases featured in Spotify
     ///Parameters:
@@ -1356,7 +3113,7 @@ impl Spotify {
         if let Some(_locale) = locale {
             params.insert("locale".to_owned(), _locale);
         }
-        if let Some(_country) = country {
+        if let Some(_country) = self.market_or_default(country)? {
             params.insert("country".to_owned(), _country.as_str().to_owned());
         }
         params.insert("limit".to_owned(), limit.to_string());
@@ -1372,13 +3129,15 @@ impl Spotify {
     /// - seed_artists - a list of artist IDs, URIs or URLs
     /// - seed_tracks - a list of artist IDs, URIs or URLs
     /// - seed_genres - a list of genre names. Available genres for
-    /// - country - An ISO 3166-1 alpha-2 country code. If provided, all
-    ///   results will be playable in this country.
+    /// - country - An ISO 3166-1 alpha-2 country code, sent as the `market`.
+    ///   The tracks are then relinked for it, with their `is_playable` and
+    ///   `linked_from` fields set, see `Recommendations::retain_playable`.
     /// - limit - The maximum number of items to return. Default: 20.
     ///   Minimum: 1. Maximum: 100
     /// - min/max/target_<attribute> - For the tuneable track attributes listed
     ///   in the documentation, these values provide filters and targeting on
-    ///   results.
+    ///   results. Values out of the documented range are rejected before
+    ///   sending the request, see `util::check_recommendation_attribute`.
     pub fn recommendations<L: Into<Option<u32>>>(
         &self,
         seed_artists: Option<Vec<String>>,
@@ -1408,7 +3167,7 @@ impl Spotify {
                 .collect();
             params.insert("seed_tracks".to_owned(), seed_tracks_ids.join(","));
         }
-        if let Some(_country) = country {
+        if let Some(_country) = self.market_or_default(country)? {
             params.insert("market".to_owned(), _country.as_str().to_owned());
         }
         let attributes = vec![
@@ -1431,6 +3190,7 @@ impl Spotify {
         for (attribute, prefix) in iproduct!(attributes, prefixes) {
             let param = prefix.to_owned() + attribute;
             if let Some(value) = payload.get(&param) {
+                check_recommendation_attribute(&param, value).map_err(invalid_parameter)?;
                 params.insert(param, value.to_string());
             }
         }
@@ -1448,8 +3208,159 @@ impl Spotify {
         let result = self.get(&url, &mut params)?;
         self.convert_result::<Recommendations>(&result)
     }
+
+    ///Get recommendations from a mixed list of seeds, see
+    ///`RecommendationSeeds`. Between 1 and 5 seeds are accepted, more or none
+    ///at all is rejected before sending the request.
+    ///Parameters:
+    ///- seeds - the artists, tracks and genres to seed from
+    ///- limit - The maximum number of items to return. Default: 20.
+    ///  Minimum: 1. Maximum: 100
+    ///- country - An ISO 3166-1 alpha-2 country code
+    ///- payload - the tuneable track attributes, as in `recommendations`
+    pub fn recommendations_with_seeds<L: Into<Option<u32>>>(
+        &self,
+        seeds: &RecommendationSeeds,
+        limit: L,
+        country: Option<Country>,
+        payload: &Map<String, Value>,
+    ) -> Result<Recommendations, failure::Error> {
+        seeds.check().map_err(invalid_parameter)?;
+        let non_empty = |seeds: &Vec<String>| Some(seeds.clone()).filter(|seeds| !seeds.is_empty());
+        self.recommendations(
+            non_empty(&seeds.artists),
+            non_empty(&seeds.genres),
+            non_empty(&seeds.tracks),
+            limit,
+            country,
+            payload,
+        )
+    }
+
+    ///Get the next tracks of an endless radio, see `RecommendationRadio`:
+    ///recommendations from the radio's seeds, without the tracks that can't
+    ///be played or that it yielded before, after which the radio is
+    ///reseeded. Spotify's picks vary between calls, but a batch of tracks
+    ///that were all yielded before comes back empty. Every call is a
+    ///request, so a radio running into the rate limit fails with
+    ///`ApiError::RateLimited` and the delay to wait, leaving the radio as
+    ///it was.
+    ///Parameters:
+    ///- radio - the radio to continue
+    ///- limit - The maximum number of items to request. Default: 20.
+    ///  Minimum: 1. Maximum: 100
+    ///- country - An ISO 3166-1 alpha-2 country code
+    pub fn radio_next<L: Into<Option<u32>>>(
+        &self,
+        radio: &mut RecommendationRadio,
+        limit: L,
+        country: Option<Country>,
+    ) -> Result<Vec<SimplifiedTrack>, failure::Error> {
+        let mut recommendations =
+            self.recommendations_with_seeds(radio.seeds(), limit, country, &Map::new())?;
+        recommendations.retain_playable();
+        Ok(radio.take_new(recommendations.tracks))
+    }
+
+    ///Get recommendations seeded with the track the user is currently
+    ///playing, built on top of `current_playback` and `recommendations`.
+    ///Fails if nothing is playing, or if the current item can't be used as a
+    ///seed (episodes and local tracks).
+    ///Parameters:
+    ///- limit - The maximum number of items to return. Default: 20.
+    ///  Minimum: 1. Maximum: 100
+    pub fn recommendations_from_current_playback<L: Into<Option<u32>>>(
+        &self,
+        limit: L,
+    ) -> Result<Recommendations, failure::Error> {
+        // ask for episodes too, otherwise an episode shows up as an empty item
+        let additional_types = vec![AdditionalType::Track, AdditionalType::Episode];
+        let playback = self.current_playback(None, Some(additional_types))?;
+        let track_id = match playback.and_then(|playback| playback.item) {
+            Some(PlayingItem::Track(track)) => match track.id {
+                Some(track_id) => track_id,
+                None => bail!("can't seed recommendations from a local track"),
+            },
+            Some(PlayingItem::Episode(_)) => {
+                bail!("can't seed recommendations from an episode")
+            }
+            None => bail!("nothing is playing right now"),
+        };
+        self.recommendations(None, None, Some(vec![track_id]), limit, None, &Map::new())
+    }
+
+    ///Get recommendations seeded with the top artists of the current user,
+    ///built on top of `current_user_top_artists` and `recommendations`. Only
+    ///the first 5 artists are used, the most seeds Spotify accepts. Fails if
+    ///the user has no top artists yet.
+    ///Parameters:
+    ///- time_range - the time frame of the top artists, default medium term
+    ///- limit - The maximum number of items to return. Default: 20.
+    ///  Minimum: 1. Maximum: 100
+    pub fn recommendations_from_top_artists<T: Into<Option<TimeRange>>, L: Into<Option<u32>>>(
+        &self,
+        time_range: T,
+        limit: L,
+    ) -> Result<Recommendations, failure::Error> {
+        let artists = self
+            .current_user_top_artists(MAX_SEEDS as u32, 0, time_range)?
+            .items;
+        if artists.is_empty() {
+            bail!("the current user has no top artists yet");
+        }
+        let seed_artists = artists
+            .into_iter()
+            .take(MAX_SEEDS)
+            .map(|artist| artist.id)
+            .collect();
+        self.recommendations(Some(seed_artists), None, None, limit, None, &Map::new())
+    }
+
+    ///Get recommendations and save them as a new playlist of the current
+    ///user, built on top of `recommendations`, `user_playlist_create` and
+    ///`user_playlist_add_tracks`.
+    ///Parameters:
+    ///- name - the name of the new playlist
+    ///- seed_artists - a list of artist IDs, URIs or URLs
+    ///- seed_genres - a list of genre names
+    ///- seed_tracks - a list of track IDs, URIs or URLs
+    ///- limit - The maximum number of tracks to add. Default: 20.
+    ///  Minimum: 1. Maximum: 100
+    ///- payload - the tuneable track attributes, as in `recommendations`
+    ///
+    ///Spotify may recommend fewer tracks than `limit`, in which case the
+    ///playlist just holds those. If nothing is recommended at all, no
+    ///playlist is created and an error is returned. The returned playlist is
+    ///fetched again after adding the tracks, so its `tracks` are populated.
+    pub fn recommendations_to_playlist<L: Into<Option<u32>>>(
+        &self,
+        name: &str,
+        seed_artists: Option<Vec<String>>,
+        seed_genres: Option<Vec<String>>,
+        seed_tracks: Option<Vec<String>>,
+        limit: L,
+        payload: &Map<String, Value>,
+    ) -> Result<FullPlaylist, failure::Error> {
+        let recommendations =
+            self.recommendations(seed_artists, seed_genres, seed_tracks, limit, None, payload)?;
+        if recommendations.tracks.is_empty() {
+            bail!("no tracks were recommended for these seeds");
+        }
+        let track_uris: Vec<String> = recommendations
+            .tracks
+            .into_iter()
+            .map(|track| track.uri)
+            .collect();
+        let user_id = self.current_user_id()?;
+        let mut playlist = self.user_playlist_create(&user_id, name, None, None)?;
+        self.user_playlist_add_tracks(&user_id, &playlist.id, &track_uris, None)?;
+        self.user_playlist(&user_id, Some(playlist.id.as_mut_str()), None, None)
+    }
+
     ///[get audio features](https://developer.spotify.com/web-api/get-audio-features/)
-    ///Get audio features for a track
+    ///Get audio features for a track. This uses the single track endpoint,
+    ///which answers with the audio features object itself; see
+    ///`audios_features` for several tracks.
     ///- track - track URI, URL or ID
     pub fn audio_features(&self, track: &str) -> Result<AudioFeatures, failure::Error> {
         let track_id = self.get_id(Type::Track, track);
@@ -1460,7 +3371,8 @@ impl Spotify {
     }
 
     ///[get several audio features](https://developer.spotify.com/web-api/get-several-audio-features/)
-    ///Get Audio Features for Several Tracks
+    ///Get Audio Features for Several Tracks, wrapped in an
+    ///`audio_features` list, even when there's a single track.
     /// -tracks a list of track URIs, URLs or IDs
     pub fn audios_features(
         &self,
@@ -1484,6 +3396,55 @@ impl Spotify {
         }
     }
 
+    ///[get several audio features](https://developer.spotify.com/web-api/get-several-audio-features/)
+    ///Get Audio Features for any number of tracks, keyed by track ID. The
+    ///tracks are requested 100 at a time (the endpoint's limit), and tracks
+    ///Spotify has no audio features for are left out of the map.
+    /// -tracks a list of track URIs, URLs or IDs
+    pub fn tracks_features_map(
+        &self,
+        tracks: &[String],
+    ) -> Result<HashMap<String, AudioFeatures>, failure::Error> {
+        let mut features_map = HashMap::new();
+        for chunk in tracks.chunks(100) {
+            let ids: Vec<String> = chunk
+                .iter()
+                .map(|track| self.get_id(Type::Track, track))
+                .collect();
+            let url = format!("audio-features/?ids={}", ids.join(","));
+            let mut dumb = HashMap::new();
+            let result = self.get(&url, &mut dumb)?;
+            // unlike `AudioFeaturesPayload`, unknown tracks come back as null
+            let mut payload =
+                self.convert_result::<HashMap<String, Vec<Option<AudioFeatures>>>>(&result)?;
+            let features = payload.remove("audio_features").unwrap_or_default();
+            for feature in features.into_iter().flatten() {
+                features_map.insert(feature.id.to_owned(), feature);
+            }
+        }
+        Ok(features_map)
+    }
+
+    ///Sort tracks by one of their audio features, lowest first, e.g. by
+    ///tempo for a smooth listening flow. Built on top of
+    ///`tracks_features_map`; tracks Spotify has no audio features for are
+    ///put at the end, in their original order.
+    ///Parameters:
+    ///- tracks - a list of track URIs, URLs or IDs
+    ///- feature - the audio feature to sort by
+    pub fn sort_tracks_by_feature(
+        &self,
+        tracks: &[String],
+        feature: AudioFeature,
+    ) -> Result<Vec<String>, failure::Error> {
+        let features = self.tracks_features_map(tracks)?;
+        let ids = tracks
+            .iter()
+            .map(|track| self.get_id(Type::Track, track))
+            .collect();
+        Ok(sort_by_feature(ids, &features, feature))
+    }
+
     ///[get audio analysis](https://developer.spotify.com/web-api/get-audio-analysis/)
     ///Get Audio Analysis for a Track
     ///Parameters:
@@ -1505,8 +3466,18 @@ impl Spotify {
         self.convert_result::<DevicePayload>(&result)
     }
 
+    ///Pick the device playback should default to: the active device if
+    ///there is one, otherwise the first device that isn't restricted.
+    ///See `DevicePayload::preferred` for the selection rules.
+    pub fn preferred_device(&self) -> Result<Option<Device>, failure::Error> {
+        let devices = self.device()?;
+        Ok(devices.preferred().cloned())
+    }
+
     ///[get informatation about the users  current playback](https://developer.spotify.com/web-api/get-information-about-the-users-current-playback/)
-    ///Get Information About The User’s Current Playback
+    ///Get Information About The User’s Current Playback, including the
+    ///device playing and its repeat and shuffle state. `None` when nothing is
+    ///playing; see `current_playing` when the device isn't needed.
     /// Parameters:
     /// - market: Optional. an ISO 3166-1 alpha-2 country code.
     /// - additional_types: Optional. A comma-separated list of item types that your client supports besides the default track type. Valid types are: `track` and `episode`.
@@ -1543,7 +3514,8 @@ impl Spotify {
     }
 
     ///[get the users currently playing track](https://developer.spotify.com/web-api/get-the-users-currently-playing-track/)
-    /// Get the User’s Currently Playing Track
+    /// Get the User’s Currently Playing Track, without the device data of
+    /// `current_playback`. `None` when nothing is playing.
     /// Query Parameters:
     /// - market: Optional. an ISO 3166-1 alpha-2 country code.
     /// - additional_types: Optional. A comma-separated list of item types that your client supports besides the default track type. Valid types are: `track` and `episode`.
@@ -1584,41 +3556,42 @@ impl Spotify {
     /// supported. Supplying more than one will return 400 Bad Request
     ///            Parameters:
     ///- device_id - transfer playback to this device
-    ///- force_play - true: after transfer, play. false:
-    ///keep current state.
-    pub fn transfer_playback<T: Into<Option<bool>>>(
-        &self,
-        device_id: &str,
-        force_play: T,
-    ) -> Result<(), failure::Error> {
-        let device_ids = vec![device_id.to_owned()];
-        let force_play = force_play.into().unwrap_or(true);
-        let mut payload = Map::new();
-        payload.insert("device_ids".to_owned(), device_ids.into());
-        payload.insert("play".to_owned(), force_play.into());
+    ///- play - true: playback continues on the new device. false: playback
+    ///  is paused there, whether it was playing before or not; Spotify's own
+    ///  default when the flag is left out. See `transfer_and_continue`.
+    pub fn transfer_playback(&self, device_id: &str, play: bool) -> Result<(), failure::Error> {
+        let payload = transfer_playback_payload(device_id, play);
         let url = String::from("me/player");
-        match self.put(&url, &Value::Object(payload)) {
+        match self.put(&url, &payload) {
             Ok(_) => Ok(()),
             Err(e) => Err(e),
         }
     }
 
+    ///Move playback to another device and keep playing there, see
+    ///`transfer_playback`.
+    ///Parameters:
+    ///- device_id - transfer playback to this device
+    pub fn transfer_and_continue(&self, device_id: &str) -> Result<(), failure::Error> {
+        self.transfer_playback(device_id, true)
+    }
+
     ///[start a users playback](https://developer.spotify.com/web-api/start-a-users-playback/)
     ///Start/Resume a User’s Playback
     ///Provide a `context_uri` to start playback or a album,
     ///artist, or playlist.
     ///
     ///Provide a `uris` list to start playback of one or more
-    ///tracks.
+    ///tracks or podcast episodes, which may be mixed.
     ///
-    ///Provide `offset` as {"position": <int>} or {"uri": "<track uri>"}
+    ///Provide `offset` as {"position": <int>} or {"uri": "<track or episode uri>"}
     ///to start playback at a particular offset.
     ///
     ///Parameters:
     ///- device_id - device target for playback
     ///- context_uri - spotify context uri to play
-    ///- uris - spotify track uris
-    ///- offset - offset into context by index or track
+    ///- uris - spotify track or episode uris
+    ///- offset - offset into context by index, track or episode
     ///- position_ms - Indicates from what position to start playback.
     pub fn start_playback(
         &self,
@@ -1659,6 +3632,30 @@ impl Spotify {
         }
     }
 
+    ///Start playback somewhere, even when nothing is playing: on the active
+    ///device, or else on the first available device that accepts Web API
+    ///commands, transferring playback to it first. The start request names
+    ///the device, so it doesn't wait for the transfer to show up in the
+    ///player state. Returns the device, or `ClientError::NoDevicesAvailable`
+    ///when the user has no device at all, or only restricted ones.
+    ///Parameters:
+    ///- context_uri - spotify context uri to play, or none to resume
+    pub fn play_on_any_available(
+        &self,
+        context_uri: Option<String>,
+    ) -> Result<Device, failure::Error> {
+        let payload = self.device()?;
+        let device = payload
+            .preferred()
+            .cloned()
+            .ok_or(ClientError::NoDevicesAvailable)?;
+        if !device.is_active {
+            self.transfer_playback(&device.id, false)?;
+        }
+        self.start_playback(Some(device.id.clone()), context_uri, None, None, None)?;
+        Ok(device)
+    }
+
     ///[pause a users playback](https://developer.spotify.com/web-api/pause-a-users-playback/)
     ///Pause a User’s Playback
     ///Parameters:
@@ -1715,6 +3712,31 @@ impl Spotify {
         }
     }
 
+    ///Seek to a fraction of the currently playing item, e.g. for a scrubber
+    ///going from 0.0 to 1.0. The fraction is clamped to that range.
+    ///Fails if nothing is playing or its duration is unknown.
+    ///            Parameters:
+    /// - fraction - position to seek to, as a fraction of the item's duration
+    /// - device_id - device target for playback
+    pub fn seek_fraction(
+        &self,
+        fraction: f64,
+        device_id: Option<String>,
+    ) -> Result<(), failure::Error> {
+        let additional_types = vec![AdditionalType::Track, AdditionalType::Episode];
+        let playback = self.current_playback(None, Some(additional_types))?;
+        let duration_ms = match playback.and_then(|playback| playback.item) {
+            Some(PlayingItem::Track(track)) => track.duration_ms,
+            Some(PlayingItem::Episode(episode)) => episode.duration_ms,
+            None => bail!("nothing is playing right now"),
+        };
+        if duration_ms == 0 {
+            bail!("the duration of the currently playing item is unknown");
+        }
+        let position_ms = fraction_to_position_ms(fraction, duration_ms);
+        self.seek_track(position_ms, device_id)
+    }
+
     ///[set repeat mode on users playback](https://developer.spotify.com/web-api/set-repeat-mode-on-users-playback/)
     ///Set Repeat Mode On User’s Playback
     ///            Parameters:
@@ -1771,6 +3793,57 @@ impl Spotify {
         }
     }
 
+    ///[get the user's queue](https://developer.spotify.com/documentation/web-api/reference/#endpoint-get-queue)
+    ///Get the currently playing item and the items in the user's queue
+    pub fn current_user_queue(&self) -> Result<CurrentUserQueue, failure::Error> {
+        let url = String::from("me/player/queue");
+        let mut dumb = HashMap::new();
+        let result = self.get(&url, &mut dumb)?;
+        self.convert_result::<CurrentUserQueue>(&result)
+    }
+
+    ///Read the current playback, the available devices and optionally the
+    ///queue, for a "now playing" view. Only a failure to read
+    ///the current playback is an error: if the devices or the queue can't be
+    ///read, they are left empty in the snapshot.
+    ///Parameters:
+    ///- include_queue - whether to read the queue too
+    pub fn playback_snapshot(
+        &self,
+        include_queue: bool,
+    ) -> Result<PlaybackSnapshot, failure::Error> {
+        let playback = self.current_playback(
+            None,
+            Some(vec![AdditionalType::Track, AdditionalType::Episode]),
+        )?;
+        let devices = self.device();
+        let queue = if include_queue {
+            Some(self.current_user_queue())
+        } else {
+            None
+        };
+        let devices = match devices {
+            Ok(payload) => Some(payload.devices),
+            Err(e) => {
+                error!("couldn't read the devices for the playback snapshot: {}", e);
+                None
+            }
+        };
+        let queue = match queue {
+            Some(Ok(queue)) => Some(queue),
+            Some(Err(e)) => {
+                error!("couldn't read the queue for the playback snapshot: {}", e);
+                None
+            }
+            None => None,
+        };
+        Ok(PlaybackSnapshot {
+            playback,
+            devices,
+            queue,
+        })
+    }
+
     ///[Add an item to the end fo the user's current playback queue](https://developer.spotify.com/console/post-queue/)
     /// Add and item to the end of the user's playback queue
     ///             Parameters:
@@ -1782,11 +3855,33 @@ impl Spotify {
         item: String,
         device_id: Option<String>,
     ) -> Result<(), failure::Error> {
-        let url = self.append_device_id(&format!("me/player/queue?uri={}", &item), device_id);
-        match self.post(&url, &json!({})) {
-            Ok(_) => Ok(()),
-            Err(e) => Err(e),
+        let mut record = None;
+        if let Some(window) = self.queue_dedup_window {
+            let key = (device_id.clone(), item.clone());
+            let mut recently_queued = self.recently_queued.lock().unwrap();
+            if !record_queued(&mut recently_queued, key.clone(), Instant::now(), window) {
+                debug!(
+                    "{} was queued less than {:?} ago, skipping it",
+                    item, window
+                );
+                return Ok(());
+            }
+            record = Some(QueuedRecord::new(&self.recently_queued, key));
+        }
+        let url = self.append_device_id(
+            &format!("me/player/queue?uri={}", encode_query_value(&item)),
+            device_id,
+        );
+        let result = self.post(&url, &json!({}));
+        if let Some(record) = record {
+            // a retry after a timeout must still be skipped, as the item may
+            // have been queued
+            match &result {
+                Err(e) if not_delivered(e) => {}
+                _ => record.keep(),
+            }
         }
+        result.map(|_| ())
     }
     /// [Save Shows for Current User](https://developer.spotify.com/console/put-current-user-saved-shows)
     /// Add a show or a list of shows to a user’s library
@@ -1820,6 +3915,14 @@ impl Spotify {
         self.convert_result::<Page<Show>>(&result)
     }
 
+    ///[get user's saved shows](https://developer.spotify.com/documentation/web-api/reference/library/get-users-saved-shows/)
+    ///Get the number of shows saved in the current user's library without
+    ///paging it, e.g. for a profile: a single show is requested, for the
+    ///`total` of its page.
+    pub fn current_user_saved_shows_count(&self) -> Result<u32, failure::Error> {
+        self.saved_count("me/shows")
+    }
+
     /// Get Spotify catalog information for a single show identified by its unique Spotify ID.
     /// [Get a show](https://developer.spotify.com/documentation/web-api/reference/shows/get-a-show/)
     /// Path Parameters:
@@ -1833,7 +3936,7 @@ impl Spotify {
     ) -> Result<FullShow, failure::Error> {
         let url = format!("shows/{}", id);
         let mut params = HashMap::new();
-        if let Some(_market) = market {
+        if let Some(_market) = self.market_or_default(market)? {
             params.insert("country".to_owned(), _market.as_str().to_owned());
         }
         let result = self.get(&url, &mut params)?;
@@ -1853,7 +3956,7 @@ impl Spotify {
         let joined_ids = ids.join(",");
         let url = "shows";
         let mut params = HashMap::new();
-        if let Some(_market) = market {
+        if let Some(_market) = self.market_or_default(market)? {
             params.insert("country".to_owned(), _market.as_str().to_owned());
         }
         params.insert("ids".to_owned(), joined_ids);
@@ -1861,13 +3964,106 @@ impl Spotify {
         self.convert_result::<SeversalSimplifiedShows>(&result)
     }
 
+    ///[get user's saved episodes](https://developer.spotify.com/documentation/web-api/reference/#endpoint-get-users-saved-episodes)
+    ///Get a list of the episodes saved in the current Spotify user's library,
+    ///needs the `user-library-read` scope.
+    ///Parameters:
+    ///- limit - the number of episodes to return
+    ///- offset - the index of the first episode to return
+    pub fn current_user_saved_episodes<L: Into<Option<u32>>, O: Into<Option<u32>>>(
+        &self,
+        limit: L,
+        offset: O,
+    ) -> Result<Page<SavedEpisode>, failure::Error> {
+        self.require_scopes(&["user-library-read"])?;
+        let mut params = HashMap::new();
+        params.insert("limit".to_owned(), limit.into().unwrap_or(20).to_string());
+        params.insert("offset".to_owned(), offset.into().unwrap_or(0).to_string());
+        let result = self.get("me/episodes", &mut params)?;
+        self.convert_result::<Page<SavedEpisode>>(&result)
+    }
+
+    ///[get user's saved episodes](https://developer.spotify.com/documentation/web-api/reference/#endpoint-get-users-saved-episodes)
+    ///Get the number of episodes saved in the current user's library without
+    ///paging it, e.g. for a profile: a single episode is requested, for the
+    ///`total` of its page.
+    pub fn current_user_saved_episodes_count(&self) -> Result<u32, failure::Error> {
+        self.require_scopes(&["user-library-read"])?;
+        self.saved_count("me/episodes")
+    }
+
+    ///[save episodes for user](https://developer.spotify.com/documentation/web-api/reference/#endpoint-save-episodes-user)
+    ///Save one or more episodes to the current user's library, 50 per
+    ///request, needs the `user-library-modify` scope.
+    ///Parameters:
+    ///- episode_ids - a list of episode URIs, URLs or IDs
+    pub fn current_user_saved_episodes_add(
+        &self,
+        episode_ids: &[String],
+    ) -> Result<(), failure::Error> {
+        self.require_scopes(&["user-library-modify"])?;
+        for chunk in episode_ids.chunks(50) {
+            let ids: Vec<String> = chunk
+                .iter()
+                .map(|id| self.get_id(Type::Episode, id))
+                .collect();
+            let url = format!("me/episodes?ids={}", ids.join(","));
+            self.put(&url, &json!({}))?;
+        }
+        Ok(())
+    }
+
+    ///[remove user's saved episodes](https://developer.spotify.com/documentation/web-api/reference/#endpoint-remove-episodes-user)
+    ///Remove one or more episodes from the current user's library, 50 per
+    ///request, needs the `user-library-modify` scope.
+    ///Parameters:
+    ///- episode_ids - a list of episode URIs, URLs or IDs
+    pub fn current_user_saved_episodes_delete(
+        &self,
+        episode_ids: &[String],
+    ) -> Result<(), failure::Error> {
+        self.require_scopes(&["user-library-modify"])?;
+        for chunk in episode_ids.chunks(50) {
+            let ids: Vec<String> = chunk
+                .iter()
+                .map(|id| self.get_id(Type::Episode, id))
+                .collect();
+            let url = format!("me/episodes?ids={}", ids.join(","));
+            self.delete(&url, &json!({}))?;
+        }
+        Ok(())
+    }
+
+    ///[check user's saved episodes](https://developer.spotify.com/documentation/web-api/reference/#endpoint-check-users-saved-episodes)
+    ///Check if one or more episodes are already saved in the current user's
+    ///library, needs the `user-library-read` scope.
+    ///Parameters:
+    ///- episode_ids - a list of episode URIs, URLs or IDs
+    pub fn current_user_saved_episodes_contains(
+        &self,
+        episode_ids: &[String],
+    ) -> Result<Vec<bool>, failure::Error> {
+        self.require_scopes(&["user-library-read"])?;
+        let ids: Vec<String> = episode_ids
+            .iter()
+            .map(|id| self.get_id(Type::Episode, id))
+            .collect();
+        self.check_contains("me/episodes/contains", &ids, 50, &HashMap::new())
+    }
+
     pub fn convert_result<'a, T: Deserialize<'a>>(
         &self,
         input: &'a str,
     ) -> Result<T, failure::Error> {
         let result = serde_json::from_str::<T>(input).map_err(|e| {
+            // the path is easier to map to a model field than the position
+            let at = match json_path_at(input, e.line(), e.column()) {
+                ref path if path.is_empty() => String::new(),
+                path => format!(" at `{}`", path),
+            };
             format_err!(
-                "convert result failed, reason: {:?}; content: [{:?}]",
+                "convert result failed{}, reason: {:?}; content: [{:?}]",
+                at,
                 e,
                 input
             )
@@ -1883,6 +4079,8 @@ impl Spotify {
     /// - limit: Optional. The maximum number of episodes to return. Default: 20. Minimum: 1. Maximum: 50.
     /// - offset: Optional. The index of the first episode to return. Default: 0 (the first object). Use with limit to get the next set of episodes.
     /// - market: Optional. An ISO 3166-1 alpha-2 country code.
+    ///
+    /// Warns when a user token can't get `SimplifiedEpisode::resume_point`.
     pub fn get_shows_episodes<L: Into<Option<u32>>, O: Into<Option<u32>>>(
         &self,
         id: String,
@@ -1890,13 +4088,14 @@ impl Spotify {
         offset: O,
         market: Option<Country>,
     ) -> Result<Page<SimplifiedEpisode>, failure::Error> {
+        self.warn_without_resume_point_scope();
         let url = format!("shows/{}/episodes", id);
         let mut params = HashMap::new();
         let limit = limit.into().unwrap_or(20);
         let offset = offset.into().unwrap_or(0);
         params.insert("limit".to_owned(), limit.to_string());
         params.insert("offset".to_owned(), offset.to_string());
-        if let Some(_market) = market {
+        if let Some(_market) = self.market_or_default(market)? {
             params.insert("country".to_owned(), _market.as_str().to_owned());
         }
         let result = self.get(&url, &mut params)?;
@@ -1909,14 +4108,17 @@ impl Spotify {
     /// - id: The Spotify ID for the episode.
     ///  Query Parameters
     /// - market: Optional. An ISO 3166-1 alpha-2 country code.
+    ///
+    /// Warns when a user token can't get `FullEpisode::resume_point`.
     pub fn get_an_episode(
         &self,
         id: String,
         market: Option<Country>,
     ) -> Result<FullEpisode, failure::Error> {
+        self.warn_without_resume_point_scope();
         let url = format!("episodes/{}", id);
         let mut params = HashMap::new();
-        if let Some(_market) = market {
+        if let Some(_market) = self.market_or_default(market)? {
             params.insert("country".to_owned(), _market.as_str().to_owned());
         }
         let result = self.get(&url, &mut params)?;
@@ -1928,15 +4130,18 @@ impl Spotify {
     /// Query Parameters
     /// - ids: Required. A comma-separated list of the Spotify IDs for the episodes. Maximum: 50 IDs.
     /// - market: Optional. An ISO 3166-1 alpha-2 country code.
+    ///
+    /// Warns when a user token can't get `FullEpisode::resume_point`.
     pub fn get_several_episodes(
         &self,
         ids: Vec<String>,
         market: Option<Country>,
     ) -> Result<SeveralEpisodes, failure::Error> {
+        self.warn_without_resume_point_scope();
         let url = "episodes";
         let joined_ids = ids.join(",");
         let mut params = HashMap::new();
-        if let Some(_market) = market {
+        if let Some(_market) = self.market_or_default(market)? {
             params.insert("country".to_owned(), _market.as_str().to_owned());
         }
         params.insert("ids".to_owned(), joined_ids);
@@ -1983,14 +4188,93 @@ impl Spotify {
         }
     }
 
+    ///Number of items of a library endpoint such as `me/tracks`, from the
+    ///`total` of a page of a single item.
+    fn saved_count(&self, url: &str) -> Result<u32, failure::Error> {
+        let mut params = HashMap::new();
+        params.insert("limit".to_owned(), "1".to_owned());
+        let result = self.get(url, &mut params)?;
+        let page = self.convert_result::<Value>(&result)?;
+        page["total"]
+            .as_u64()
+            .map(|total| total as u32)
+            .ok_or_else(|| format_err!("missing total in response: [{:?}]", result))
+    }
+
+    ///The given market, or the client's `default_market` if there's none.
+    fn market_or_default(
+        &self,
+        market: Option<Country>,
+    ) -> Result<Option<Country>, failure::Error> {
+        let market = market.or(self.default_market);
+        if market == Some(Country::FromToken) && !self.has_user_token() {
+            return Err(ClientError::InvalidParameter(
+                "market `from_token` needs a user access token, but the client only has client credentials"
+                    .to_owned(),
+            )
+            .into());
+        }
+        Ok(market)
+    }
+
+    ///Whether requests are made on behalf of a user. A token passed with
+    ///`access_token` is assumed to be a user token, as is the token info of
+    ///the client credentials manager; otherwise the client credentials flow
+    ///only gives an app token.
+    fn has_user_token(&self) -> bool {
+        match (&self.access_token, &self.client_credentials_manager) {
+            (Some(_), _) => true,
+            (None, Some(manager)) => manager.token_info.is_some(),
+            (None, None) => false,
+        }
+    }
+
+    ///Check that the token was granted `scopes` before calling an endpoint
+    ///that needs them. A token passed with `access_token` can't be checked,
+    ///so it is let through.
+    fn require_scopes(&self, scopes: &[&str]) -> Result<(), failure::Error> {
+        let token_info = match self.client_credentials_manager {
+            Some(ref manager) => match manager.token_info {
+                Some(ref token_info) if self.access_token.is_none() => token_info,
+                _ => return Ok(()),
+            },
+            None => return Ok(()),
+        };
+        let granted: Vec<&str> = token_info.scope.split_whitespace().collect();
+        let missing: Vec<String> = scopes
+            .iter()
+            .filter(|scope| !granted.contains(scope))
+            .map(|scope| (*scope).to_owned())
+            .collect();
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(ClientError::InsufficientScopes(missing).into())
+        }
+    }
+
+    ///`resume_point` of episodes is only filled in for a user token with the
+    ///`user-read-playback-position` scope, Spotify silently leaves it out
+    ///otherwise. Logs a warning when a user token lacks it, app tokens never
+    ///get it.
+    fn warn_without_resume_point_scope(&self) {
+        if self.has_user_token()
+            && self
+                .require_scopes(&["user-read-playback-position"])
+                .is_err()
+        {
+            warn!("the token lacks the user-read-playback-position scope, episodes won't have a resume_point");
+        }
+    }
+
     ///Append device ID to API path.
     fn append_device_id(&self, path: &str, device_id: Option<String>) -> String {
         let mut new_path = path.to_string();
         if let Some(_device_id) = device_id {
             if path.contains('?') {
-                new_path.push_str(&format!("&device_id={}", _device_id));
+                new_path.push_str(&format!("&device_id={}", encode_query_value(&_device_id)));
             } else {
-                new_path.push_str(&format!("?device_id={}", _device_id));
+                new_path.push_str(&format!("?device_id={}", encode_query_value(&_device_id)));
             }
         }
         new_path
@@ -2076,6 +4360,488 @@ mod tests {
         );
     }
     #[test]
+    fn test_request_headers() {
+        let headers = request_headers("Bearer test-access", true);
+        assert_eq!(headers[AUTHORIZATION], "Bearer test-access");
+        assert_eq!(headers[CONTENT_TYPE], "application/json");
+        assert_eq!(headers[ACCEPT], "application/json");
+        let headers = request_headers("Bearer test-access", false);
+        assert!(!headers.contains_key(CONTENT_TYPE));
+        assert_eq!(headers[ACCEPT], "application/json");
+    }
+    #[test]
+    fn test_user_client_builder() {
+        let builder = UserClientBuilder {
+            oauth: SpotifyOAuth::default()
+                .client_id("test-id")
+                .client_secret("test-secret"),
+        };
+        let spotify = builder.build(TokenInfo::default().access_token("test-access"));
+        let manager = spotify.client_credentials_manager.unwrap();
+        assert_eq!(manager.client_id, "test-id");
+        assert_eq!(manager.client_secret, "test-secret");
+        assert_eq!(manager.token_info.unwrap().access_token, "test-access");
+    }
+    #[test]
+    fn test_recommendations_attribute_out_of_range() {
+        let spotify = Spotify::default().access_token("test-access").build();
+        let mut payload = Map::new();
+        payload.insert("target_popularity".to_owned(), 101.into());
+        let err = spotify
+            .recommendations(
+                None,
+                Some(vec!["rock".to_owned()]),
+                None,
+                None,
+                None,
+                &payload,
+            )
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast::<ClientError>(),
+            Ok(ClientError::InvalidParameter(_))
+        ));
+    }
+    #[test]
+    fn test_recommendations_without_seeds() {
+        let spotify = Spotify::default().access_token("test-access").build();
+        let err = spotify
+            .recommendations_with_seeds(&RecommendationSeeds::default(), None, None, &Map::new())
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast::<ClientError>(),
+            Ok(ClientError::InvalidParameter(_))
+        ));
+    }
+    #[test]
+    fn test_fetch_all_pages() {
+        // pages of two of the numbers up to 4
+        let numbers = |offset: u32| -> Page<u32> {
+            let items: Vec<u32> = (offset..5).take(2).collect();
+            let next = if offset + 2 < 5 {
+                Some("next".to_owned())
+            } else {
+                None
+            };
+            Page {
+                href: String::new(),
+                items,
+                limit: 2,
+                next,
+                offset,
+                previous: None,
+                total: 5,
+            }
+        };
+        let mut offsets = Vec::new();
+        let all: Result<Vec<u32>, _> = fetch_all_pages(|offset| {
+            offsets.push(offset);
+            Ok(numbers(offset))
+        })
+        .collect();
+        assert_eq!(all.unwrap(), vec![0, 1, 2, 3, 4]);
+        assert_eq!(offsets, vec![0, 2, 4]);
+        // stopping early doesn't request the next pages
+        offsets.clear();
+        let first = fetch_all_pages(|offset| {
+            offsets.push(offset);
+            Ok(numbers(offset))
+        })
+        .next();
+        assert_eq!(first.unwrap().unwrap(), 0);
+        assert_eq!(offsets, vec![0]);
+    }
+    #[test]
+    fn test_queue_record_kept_unless_not_delivered() {
+        use std::io::Read;
+        // reads the request and drops the connection without answering, as
+        // on a reset after Spotify got it
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let prefix = format!("http://{}/", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let mut received = 0;
+            for stream in listener.incoming().take(2) {
+                let mut stream = stream.unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0; 1024];
+                while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                    match stream.read(&mut buf) {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => request.extend_from_slice(&buf[..n]),
+                    }
+                }
+                received += 1;
+            }
+            received
+        });
+        let spotify = Spotify::default()
+            .prefix(&prefix)
+            .access_token("test-access")
+            .queue_dedup_window(Duration::from_secs(60))
+            .build();
+        let item = "spotify:track:1".to_owned();
+        assert!(spotify.add_item_to_queue(item.clone(), None).is_err());
+        // the retry is skipped, the item may be queued already
+        assert!(spotify.add_item_to_queue(item.clone(), None).is_ok());
+        // the other item connects to stop the server
+        assert!(spotify
+            .add_item_to_queue("spotify:track:2".to_owned(), None)
+            .is_err());
+        assert_eq!(server.join().unwrap(), 2);
+
+        // nothing listens, so the request was never delivered
+        let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let prefix = format!("http://{}/", closed.local_addr().unwrap());
+        drop(closed);
+        let spotify = Spotify::default()
+            .prefix(&prefix)
+            .access_token("test-access")
+            .queue_dedup_window(Duration::from_secs(60))
+            .build();
+        assert!(spotify.add_item_to_queue(item, None).is_err());
+        assert!(spotify.recently_queued.lock().unwrap().is_empty());
+    }
+    #[test]
+    fn test_playlist_tracks_sends_market() {
+        let page = r#"{"href": "h", "items": [], "limit": 1, "next": null, "offset": 0, "previous": null, "total": 0}"#;
+        let (prefix, server) = serve(&[page, page]);
+        let spotify = Spotify::default()
+            .prefix(&prefix)
+            .access_token("test-access")
+            .build();
+        spotify
+            .playlist_tracks("pl", None, 1, None, Some(Country::Spain))
+            .unwrap();
+        spotify
+            .user_playlist_tracks("owner", "pl", None, 1, None, Some(Country::Spain))
+            .unwrap();
+        for request in server.join().unwrap() {
+            let path = request.split(' ').nth(1).unwrap();
+            let url = url::Url::parse(&format!("http://localhost{}", path)).unwrap();
+            assert_eq!(url.path(), "/playlists/pl/tracks");
+            assert!(url
+                .query_pairs()
+                .any(|(key, value)| key == "market" && value == "ES"));
+        }
+    }
+    #[test]
+    fn test_playlist_tracks_all_keeps_fields() {
+        // Spotify's next links leave `fields` out
+        let (prefix, server) = serve(&[
+            r#"{"items": [{"n": 1}], "next": "{prefix}playlists/pl/tracks?offset=1&limit=1"}"#,
+            r#"{"items": [{"n": 2}], "next": null}"#,
+        ]);
+        let spotify = Spotify::default()
+            .prefix(&prefix)
+            .access_token("test-access")
+            .build();
+        let items: Vec<Value> = spotify
+            .playlist_tracks_all("pl", Some("items(n)"), None)
+            .unwrap();
+        assert_eq!(items, vec![json!({"n": 1}), json!({"n": 2})]);
+        let requests = server.join().unwrap();
+        // the `fields` of the request line, decoded
+        let fields = |request: &str| -> Option<String> {
+            let path = request.split(' ').nth(1).unwrap();
+            url::Url::parse(&format!("http://localhost{}", path))
+                .unwrap()
+                .query_pairs()
+                .find(|(key, _)| key == "fields")
+                .map(|(_, value)| value.into_owned())
+        };
+        assert_eq!(fields(&requests[0]).unwrap(), "items(n),next");
+        assert!(requests[1].starts_with("GET /playlists/pl/tracks?offset=1&limit=1&"));
+        assert_eq!(fields(&requests[1]).unwrap(), "items(n),next");
+    }
+    #[test]
+    fn test_contains_keeps_input_order() {
+        // every third track is saved
+        let saved: Vec<bool> = (0..51).map(|i| i % 3 == 0).collect();
+        let first = serde_json::to_string(&saved[..50]).unwrap();
+        let second = serde_json::to_string(&saved[50..]).unwrap();
+        let (prefix, server) = serve(&[&first, &second]);
+        let spotify = Spotify::default()
+            .prefix(&prefix)
+            .access_token("test-access")
+            .build();
+        let track_ids: Vec<String> = (0..51).map(|i| format!("track{}", i)).collect();
+        let contains = spotify
+            .current_user_saved_tracks_contains(&track_ids)
+            .unwrap();
+        assert_eq!(contains, saved);
+        let requests = server.join().unwrap();
+        assert!(requests[0].contains("ids=track0,track1,"));
+        assert!(requests[0].contains(",track49 "));
+        assert!(requests[1].starts_with("GET /me/tracks/contains?ids=track50 "));
+    }
+    /// Answers one request with an empty JSON object, the server thread
+    /// returns the request it received
+    fn serve_once() -> (String, std::thread::JoinHandle<String>) {
+        let (prefix, server) = serve(&["{}"]);
+        let request = std::thread::spawn(move || server.join().unwrap().remove(0));
+        (prefix, request)
+    }
+    /// Answers one request per response, in order, with the given JSON
+    /// bodies, in which `{prefix}` is replaced by the server's URL, e.g. for
+    /// `next` links; the server thread returns the requests it received,
+    /// head and body
+    fn serve(responses: &[&str]) -> (String, std::thread::JoinHandle<Vec<String>>) {
+        use std::io::{Read, Write};
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let prefix = format!("http://{}/", listener.local_addr().unwrap());
+        let responses: Vec<String> = responses
+            .iter()
+            .map(|r| r.replace("{prefix}", &prefix))
+            .collect();
+        let server = std::thread::spawn(move || {
+            responses
+                .iter()
+                .map(|response| {
+                    let (mut stream, _) = listener.accept().unwrap();
+                    let mut request = Vec::new();
+                    let mut buf = [0; 1024];
+                    let head_len = loop {
+                        if let Some(end) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                            break end + 4;
+                        }
+                        let n = stream.read(&mut buf).unwrap();
+                        request.extend_from_slice(&buf[..n]);
+                    };
+                    let head = String::from_utf8_lossy(&request[..head_len]).to_lowercase();
+                    let body_len: usize = head
+                        .lines()
+                        .find_map(|line| line.strip_prefix("content-length: "))
+                        .map_or(0, |len| len.parse().unwrap());
+                    while request.len() < head_len + body_len {
+                        let n = stream.read(&mut buf).unwrap();
+                        request.extend_from_slice(&buf[..n]);
+                    }
+                    write!(
+                        stream,
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                         Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        response.len(),
+                        response
+                    )
+                    .unwrap();
+                    String::from_utf8_lossy(&request).into_owned()
+                })
+                .collect()
+        });
+        (prefix, server)
+    }
+    #[test]
+    fn test_add_tracks_chunks() {
+        let snapshot = r#"{"snapshot_id": "snap"}"#;
+        let (prefix, server) = serve(&[snapshot, snapshot, snapshot]);
+        let spotify = Spotify::default()
+            .prefix(&prefix)
+            .access_token("test-access")
+            .build();
+        let track_ids: Vec<String> = (0..201).map(|i| format!("track{}", i)).collect();
+        let result = spotify
+            .user_playlist_add_tracks("user", "playlist", &track_ids, Some(5))
+            .unwrap();
+        assert_eq!(result.snapshot_id, "snap");
+        let bodies: Vec<Value> = server
+            .join()
+            .unwrap()
+            .iter()
+            .map(|request| {
+                serde_json::from_str(&request[request.find("\r\n\r\n").unwrap() + 4..]).unwrap()
+            })
+            .collect();
+        let chunks: Vec<(usize, i64)> = bodies
+            .iter()
+            .map(|body| {
+                (
+                    body["uris"].as_array().unwrap().len(),
+                    body["position"].as_i64().unwrap(),
+                )
+            })
+            .collect();
+        // each chunk is inserted after the previous ones
+        assert_eq!(chunks, vec![(100, 5), (100, 105), (1, 205)]);
+        assert_eq!(bodies[2]["uris"][0], "spotify:track:track200");
+    }
+    #[test]
+    fn test_remove_tracks_threads_snapshot() {
+        let (prefix, server) = serve(&[
+            r#"{"snapshot_id": "snap-1"}"#,
+            r#"{"snapshot_id": "snap-2"}"#,
+        ]);
+        let spotify = Spotify::default()
+            .prefix(&prefix)
+            .access_token("test-access")
+            .build();
+        let track_ids: Vec<String> = (0..101).map(|i| format!("track{}", i)).collect();
+        let result = spotify
+            .user_playlist_remove_all_occurrences_of_tracks("user", "playlist", &track_ids, None)
+            .unwrap();
+        assert_eq!(result.snapshot_id, "snap-2");
+        let requests = server.join().unwrap();
+        let body = |request: &str| -> Value {
+            serde_json::from_str(&request[request.find("\r\n\r\n").unwrap() + 4..]).unwrap()
+        };
+        let first = body(&requests[0]);
+        assert_eq!(first["tracks"].as_array().unwrap().len(), 100);
+        assert!(first.get("snapshot_id").is_none());
+        // the second chunk is removed from the snapshot the first one returned
+        let second = body(&requests[1]);
+        assert_eq!(second["tracks"].as_array().unwrap().len(), 1);
+        assert_eq!(second["snapshot_id"], "snap-1");
+    }
+    #[test]
+    fn test_accept_header_sent() {
+        let (prefix, server) = serve_once();
+        let spotify = Spotify::default()
+            .prefix(&prefix)
+            .access_token("test-access")
+            .build();
+        spotify.get("me", &mut HashMap::new()).unwrap();
+        let request = server.join().unwrap().to_lowercase();
+        assert!(request.contains("\r\naccept: application/json\r\n"));
+    }
+    #[test]
+    fn test_query_encoding() {
+        let (prefix, server) = serve_once();
+        let spotify = Spotify::default()
+            .prefix(&prefix)
+            .access_token("test-access")
+            .build();
+        // the empty response isn't a search result
+        let result = spotify.search(
+            "artist:\"Simon & Garfunkel\" +1 #2 100% é",
+            SearchType::Track,
+            1,
+            0,
+            Some(Country::Spain),
+            None,
+        );
+        assert!(result.is_err());
+        let request = server.join().unwrap();
+        assert_eq!(
+            request.lines().next().unwrap(),
+            "GET /search?limit=1&market=ES&offset=0\
+             &q=artist:%22Simon%20%26%20Garfunkel%22%20%2B1%20%232%20100%25%20%C3%A9\
+             &type=track HTTP/1.1"
+        );
+    }
+    #[test]
+    fn test_timeouts() {
+        let spotify = Spotify::default().access_token("test-access").build();
+        assert!(spotify.http.is_none());
+        let spotify = spotify
+            .connect_timeout(Duration::from_secs(2))
+            .timeout(Duration::from_secs(30))
+            .build();
+        assert_eq!(spotify.connect_timeout, Some(Duration::from_secs(2)));
+        assert_eq!(spotify.timeout, Some(Duration::from_secs(30)));
+        assert!(spotify.clone().http.is_some());
+    }
+    #[test]
+    fn test_unsafe_writes_retried_only_when_not_connected() {
+        use std::io::Read;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        // reads every request and drops the connection without answering
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let prefix = format!("http://{}/", listener.local_addr().unwrap());
+        let received = Arc::new(AtomicUsize::new(0));
+        let counter = received.clone();
+        std::thread::spawn(move || {
+            'connections: for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0; 1024];
+                // the head, and whatever part of the body came with it
+                while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                    match stream.read(&mut buf) {
+                        Ok(0) | Err(_) => continue 'connections,
+                        Ok(n) => request.extend_from_slice(&buf[..n]),
+                    }
+                }
+                counter.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+        let spotify = Spotify::default()
+            .prefix(&prefix)
+            .access_token("test-access")
+            .network_retries(1, Duration::from_millis(0))
+            .timeout(Duration::from_secs(5))
+            .build();
+        let reorder = spotify.user_playlist_recorder_tracks("user", "playlist", 0, 1, 3, None);
+        assert!(reorder.is_err());
+        assert_eq!(received.load(Ordering::SeqCst), 1);
+        let reorder = spotify.user_playlist_recorder_tracks(
+            "user",
+            "playlist",
+            0,
+            1,
+            3,
+            Some("snapshot".to_owned()),
+        );
+        assert!(reorder.is_err());
+        assert_eq!(received.load(Ordering::SeqCst), 3);
+        let mut track = Map::new();
+        track.insert("uri".to_owned(), "spotify:track:a".into());
+        track.insert("position".to_owned(), 0.into());
+        let remove = spotify.user_playlist_remove_specific_occurrenes_of_tracks(
+            "user",
+            "playlist",
+            vec![track.clone()],
+            None,
+        );
+        assert!(remove.is_err());
+        assert_eq!(received.load(Ordering::SeqCst), 4);
+        let remove = spotify.user_playlist_remove_specific_occurrenes_of_tracks(
+            "user",
+            "playlist",
+            vec![track],
+            Some("snapshot".to_owned()),
+        );
+        assert!(remove.is_err());
+        assert_eq!(received.load(Ordering::SeqCst), 6);
+        // raw writes may go to any endpoint, so they're never replayed
+        assert!(spotify.put_raw("me/player/play", &json!({})).is_err());
+        assert!(spotify.delete_raw("me/tracks", &json!({})).is_err());
+        assert_eq!(received.load(Ordering::SeqCst), 8);
+    }
+    #[test]
+    fn test_raw_request_to_foreign_host() {
+        let spotify = Spotify::default().access_token("test-access").build();
+        let err = spotify
+            .get_raw("https://example.com/v1/me", &mut HashMap::new())
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast::<ClientError>(),
+            Ok(ClientError::InvalidParameter(_))
+        ));
+    }
+    /// The profile of the user `id`, as cached by `me`
+    fn cached_user(id: &str) -> PrivateUser {
+        serde_json::from_value(json!({
+            "external_urls": {},
+            "href": format!("https://api.spotify.com/v1/users/{}", id),
+            "id": id,
+            "type": "user",
+            "uri": format!("spotify:user:{}", id)
+        }))
+        .unwrap()
+    }
+    #[test]
+    fn test_set_user_token_keeps_same_user() {
+        let mut spotify = Spotify::default().access_token("test-access").build();
+        *spotify.user_cache.lock().unwrap() = Some(cached_user("wizzler"));
+        // a refreshed token of the same user
+        spotify.set_user_token("refreshed-access", "wizzler");
+        assert_eq!(spotify.access_token, Some("refreshed-access".to_owned()));
+        assert!(spotify.user_cache.lock().unwrap().is_some());
+        // a token of another user
+        spotify.set_user_token("other-access", "spotify");
+        assert_eq!(spotify.access_token, Some("other-access".to_owned()));
+        assert!(spotify.user_cache.lock().unwrap().is_none());
+    }
+    #[test]
     fn test_get_uri() {
         let spotify = Spotify::default().access_token("test-access").build();
         let track_id1 = "spotify:track:4iV5W9uYEdYUVa79Axb7Rh";
@@ -3,20 +3,25 @@
 use chrono::prelude::*;
 use dotenv::dotenv;
 use percent_encoding::{utf8_percent_encode, PATH_SEGMENT_ENCODE_SET};
-use reqwest::blocking::Client;
+use reqwest::blocking::{Client, RequestBuilder};
 use serde_json;
 
 // use built-in library
 use std::collections::{HashMap, HashSet};
 use std::env;
+use std::fmt;
 use std::fs::File;
 use std::fs::OpenOptions;
 use std::io::prelude::*;
 use std::iter::FromIterator;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 
 // use customized library
-use super::util::{convert_map_to_string, datetime_to_timestamp, generate_random_string};
+use super::util::{
+    convert_map_to_string, datetime_to_timestamp, generate_random_string, warn_if_in_async_runtime,
+};
+use crate::util::{read_env_vars, read_vars};
 
 /// Client credentials object for spotify
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -24,6 +29,16 @@ pub struct SpotifyClientCredentials {
     pub client_id: String,
     pub client_secret: String,
     pub token_info: Option<TokenInfo>,
+    /// App token obtained through the client-credentials flow. It's shared
+    /// between clones, so every `Spotify` built from the same credentials
+    /// reuses it until it expires.
+    #[serde(skip)]
+    app_token: Arc<Mutex<Option<TokenInfo>>>,
+    #[serde(skip)]
+    on_token_refresh: Option<TokenCallback>,
+    /// Where tokens are requested, `TOKEN_URL` but in tests
+    #[serde(skip, default = "default_token_url")]
+    token_url: String,
 }
 /// Authorization for spotify
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -35,6 +50,29 @@ pub struct SpotifyOAuth {
     pub cache_path: PathBuf,
     pub scope: String,
     pub proxies: Option<String>,
+    #[serde(skip)]
+    on_token_refresh: Option<TokenCallback>,
+    /// Where tokens are requested, `TOKEN_URL` but in tests
+    #[serde(skip, default = "default_token_url")]
+    token_url: String,
+}
+
+/// Endpoint of the token requests of every flow
+const TOKEN_URL: &str = "https://accounts.spotify.com/api/token";
+fn default_token_url() -> String {
+    TOKEN_URL.to_owned()
+}
+
+/// Callback run with every token that is obtained or refreshed, see
+/// `SpotifyOAuth::on_token_refresh`. Its error is returned instead of the
+/// token.
+#[derive(Clone)]
+pub struct TokenCallback(Arc<TokenRefreshFn>);
+type TokenRefreshFn = dyn Fn(&TokenInfo) -> Result<(), failure::Error> + Send + Sync;
+impl fmt::Debug for TokenCallback {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("TokenCallback")
+    }
 }
 
 /// Spotify token-info
@@ -89,6 +127,11 @@ impl TokenInfo {
     pub fn set_refresh_token(&mut self, refresh_token: &str) {
         self.refresh_token = Some(refresh_token.to_owned());
     }
+    /// `expires_at` as a date, `None` if it isn't known
+    pub fn expires_at_datetime(&self) -> Option<DateTime<Utc>> {
+        self.expires_at
+            .and_then(|expires_at| Utc.timestamp_opt(expires_at, 0).single())
+    }
 }
 
 impl SpotifyClientCredentials {
@@ -106,8 +149,20 @@ impl SpotifyClientCredentials {
             client_id,
             client_secret,
             token_info: None,
+            app_token: Arc::new(Mutex::new(None)),
+            on_token_refresh: None,
+            token_url: default_token_url(),
         }
     }
+    /// build SpotifyClientCredentials from the `RSPOTIFY_CLIENT_ID` and
+    /// `RSPOTIFY_CLIENT_SECRET` environment variables (or `.env` file). Fails
+    /// naming the variables that are missing.
+    pub fn from_env() -> Result<SpotifyClientCredentials, failure::Error> {
+        let vars = read_env_vars(&["RSPOTIFY_CLIENT_ID", "RSPOTIFY_CLIENT_SECRET"])?;
+        Ok(SpotifyClientCredentials::default()
+            .client_id(&vars[0])
+            .client_secret(&vars[1]))
+    }
     pub fn client_id(mut self, client_id: &str) -> SpotifyClientCredentials {
         self.client_id = client_id.to_owned();
         self
@@ -116,6 +171,19 @@ impl SpotifyClientCredentials {
         self.client_secret = client_secret.to_owned();
         self
     }
+    /// run `callback` with every app token requested through the
+    /// client-credentials flow, before it's used. When it fails, e.g. to
+    /// persist the token, the request that needed the token fails with its
+    /// error.
+    pub fn on_token_refresh<
+        F: Fn(&TokenInfo) -> Result<(), failure::Error> + Send + Sync + 'static,
+    >(
+        mut self,
+        callback: F,
+    ) -> SpotifyClientCredentials {
+        self.on_token_refresh = Some(TokenCallback(Arc::new(callback)));
+        self
+    }
     pub fn token_info(mut self, token_info: TokenInfo) -> SpotifyClientCredentials {
         self.token_info = Some(token_info);
         self
@@ -142,34 +210,56 @@ impl SpotifyClientCredentials {
         self
     }
     /// get access token from self.token_info, if self.token_info is none or is
-    /// expired. fetch token info by HTTP request
-    pub fn get_access_token(&self) -> String {
-        let access_token = match self.token_info {
-            Some(ref token_info) => {
-                if !self.is_token_expired(token_info) {
-                    debug!("token info: {:?}", &token_info);
-                    Some(&token_info.access_token)
-                } else {
-                    None
-                }
+    /// expired, fall back to the app token of the client-credentials flow.
+    /// The app token is cached and, since this flow has no refresh token,
+    /// requested again once it expires.
+    pub fn get_access_token(&self) -> Result<String, failure::Error> {
+        if let Some(ref token_info) = self.token_info {
+            if !self.is_token_expired(token_info) {
+                debug!("token info: {:?}", &token_info);
+                return Ok(token_info.access_token.to_owned());
             }
-            None => None,
-        };
-        match access_token {
-            Some(access_token) => access_token.to_owned(),
-            None => match self.request_access_token() {
-                Some(new_token_info) => {
-                    debug!("token info: {:?}", &new_token_info);
-                    new_token_info.access_token
-                }
-                None => String::new(),
-            },
+        }
+        if let Some(token_info) = self.cached_app_token() {
+            debug!("cached app token info: {:?}", &token_info);
+            return Ok(token_info.access_token);
+        }
+        match self.request_access_token()? {
+            Some(new_token_info) => {
+                debug!("token info: {:?}", &new_token_info);
+                let access_token = new_token_info.access_token.to_owned();
+                *self.app_token.lock().unwrap() = Some(new_token_info);
+                Ok(access_token)
+            }
+            None => Ok(String::new()),
+        }
+    }
+    /// the cached app token, if it hasn't expired yet
+    fn cached_app_token(&self) -> Option<TokenInfo> {
+        let app_token = self.app_token.lock().unwrap();
+        match *app_token {
+            Some(ref token_info) if !self.is_token_expired(token_info) => Some(token_info.clone()),
+            _ => None,
+        }
+    }
+    /// When the token in use expires: the expiry of `token_info` when it's
+    /// set, otherwise the one of the app token obtained through the
+    /// client-credentials flow, if one was obtained yet.
+    pub fn token_expires_at(&self) -> Option<DateTime<Utc>> {
+        match self.token_info {
+            Some(ref token_info) => token_info.expires_at_datetime(),
+            None => self
+                .app_token
+                .lock()
+                .unwrap()
+                .as_ref()
+                .and_then(TokenInfo::expires_at_datetime),
         }
     }
     fn is_token_expired(&self, token_info: &TokenInfo) -> bool {
         is_token_expired(token_info)
     }
-    fn request_access_token(&self) -> Option<TokenInfo> {
+    fn request_access_token(&self) -> Result<Option<TokenInfo>, failure::Error> {
         let mut payload = HashMap::new();
         payload.insert("grant_type", "client_credentials");
         if let Some(mut token_info) =
@@ -177,9 +267,12 @@ impl SpotifyClientCredentials {
         {
             let expires_in = token_info.expires_in;
             token_info.set_expires_at(datetime_to_timestamp(expires_in));
-            Some(token_info)
+            if let Some(ref callback) = self.on_token_refresh {
+                (callback.0)(&token_info)?;
+            }
+            Ok(Some(token_info))
         } else {
-            None
+            Ok(None)
         }
     }
     fn fetch_access_token(
@@ -188,7 +281,7 @@ impl SpotifyClientCredentials {
         client_secret: &str,
         payload: &HashMap<&str, &str>,
     ) -> Option<TokenInfo> {
-        fetch_access_token(client_id, client_secret, payload)
+        fetch_access_token(&self.token_url, client_id, client_secret, payload)
     }
 }
 
@@ -215,8 +308,38 @@ impl SpotifyOAuth {
             scope: String::new(),
             cache_path: PathBuf::from(".spotify_token_cache.json"),
             proxies: None,
+            on_token_refresh: None,
+            token_url: default_token_url(),
         }
     }
+    /// build SpotifyOAuth from the `RSPOTIFY_CLIENT_ID`,
+    /// `RSPOTIFY_CLIENT_SECRET` and `RSPOTIFY_REDIRECT_URI` environment
+    /// variables (or `.env` file), plus the optional space-delimited
+    /// `RSPOTIFY_SCOPES`. Fails naming the required variables that are
+    /// missing.
+    pub fn from_env() -> Result<SpotifyOAuth, failure::Error> {
+        dotenv().ok();
+        SpotifyOAuth::from_lookup(|name| env::var(name).ok())
+    }
+    /// `from_env` with the variables read through `lookup`
+    fn from_lookup(
+        lookup: impl Fn(&str) -> Option<String>,
+    ) -> Result<SpotifyOAuth, failure::Error> {
+        let vars = read_vars(
+            &[
+                "RSPOTIFY_CLIENT_ID",
+                "RSPOTIFY_CLIENT_SECRET",
+                "RSPOTIFY_REDIRECT_URI",
+            ],
+            &lookup,
+        )?;
+        let scope = lookup("RSPOTIFY_SCOPES").unwrap_or_default();
+        Ok(SpotifyOAuth::default()
+            .client_id(&vars[0])
+            .client_secret(&vars[1])
+            .redirect_uri(&vars[2])
+            .scope(&scope))
+    }
     pub fn client_id(mut self, client_id: &str) -> SpotifyOAuth {
         self.client_id = client_id.to_owned();
         self
@@ -241,6 +364,19 @@ impl SpotifyOAuth {
         self.cache_path = cache_path;
         self
     }
+    /// run `callback` with every token obtained from a code or refreshed,
+    /// before it's cached and returned, e.g. to persist it somewhere else
+    /// than `cache_path`. When it fails, its error is returned instead of the
+    /// token, which isn't cached.
+    pub fn on_token_refresh<
+        F: Fn(&TokenInfo) -> Result<(), failure::Error> + Send + Sync + 'static,
+    >(
+        mut self,
+        callback: F,
+    ) -> SpotifyOAuth {
+        self.on_token_refresh = Some(TokenCallback(Arc::new(callback)));
+        self
+    }
     pub fn proxies(mut self, proxies: &str) -> SpotifyOAuth {
         self.proxies = Some(proxies.to_owned());
         self
@@ -269,43 +405,49 @@ impl SpotifyOAuth {
         }
         self
     }
-    pub fn get_cached_token(&mut self) -> Option<TokenInfo> {
+    pub fn get_cached_token(&mut self) -> Result<Option<TokenInfo>, failure::Error> {
         let display = self.cache_path.display();
         let mut file = match File::open(&self.cache_path) {
             Ok(file) => file,
             Err(why) => {
                 error!("couldn't open {}: {:?}", display, why.to_string());
-                return None;
+                return Ok(None);
             }
         };
         let mut token_info_string = String::new();
         match file.read_to_string(&mut token_info_string) {
             Err(why) => {
                 error!("couldn't read {}: {}", display, why.to_string());
-                None
+                Ok(None)
             }
             Ok(_) => {
                 let mut token_info: TokenInfo = serde_json::from_str(&token_info_string)
                     .unwrap_or_else(|_| {
                         panic!("convert [{:?}] to json failed", self.cache_path.display())
                     });
+                if token_info.access_token.trim().is_empty() {
+                    warn!("the token cached in {} has no access token", display);
+                }
                 if !SpotifyOAuth::is_scope_subset(&mut self.scope, &mut token_info.scope) {
-                    None
+                    Ok(None)
                 } else if self.is_token_expired(&token_info) {
                     if let Some(refresh_token) = token_info.refresh_token {
                         self.refresh_access_token(&refresh_token)
                     } else {
-                        None
+                        Ok(None)
                     }
                 } else {
-                    Some(token_info)
+                    Ok(Some(token_info))
                 }
             }
         }
     }
     /// gets the access_token for the app with given the code without caching token.
 
-    pub fn get_access_token_without_cache(&self, code: &str) -> Option<TokenInfo> {
+    pub fn get_access_token_without_cache(
+        &self,
+        code: &str,
+    ) -> Result<Option<TokenInfo>, failure::Error> {
         let mut payload: HashMap<&str, &str> = HashMap::new();
         payload.insert("redirect_uri", &self.redirect_uri);
         payload.insert("code", code);
@@ -315,13 +457,13 @@ impl SpotifyOAuth {
         return self.fetch_access_token(&self.client_id, &self.client_secret, &payload);
     }
     /// gets the access_token for the app with given the code
-    pub fn get_access_token(&self, code: &str) -> Option<TokenInfo> {
-        if let Some(token_info) = self.get_access_token_without_cache(code) {
+    pub fn get_access_token(&self, code: &str) -> Result<Option<TokenInfo>, failure::Error> {
+        if let Some(token_info) = self.get_access_token_without_cache(code)? {
             match serde_json::to_string(&token_info) {
                 Ok(token_info_string) => {
                     trace!("get_access_token->token_info[{:?}]", &token_info_string);
                     self.save_token_info(&token_info_string);
-                    Some(token_info)
+                    Ok(Some(token_info))
                 }
                 Err(why) => {
                     panic!(
@@ -331,7 +473,7 @@ impl SpotifyOAuth {
                 }
             }
         } else {
-            None
+            Ok(None)
         }
     }
     /// fetch access_token
@@ -340,9 +482,13 @@ impl SpotifyOAuth {
         client_id: &str,
         client_secret: &str,
         payload: &HashMap<&str, &str>,
-    ) -> Option<TokenInfo> {
+    ) -> Result<Option<TokenInfo>, failure::Error> {
         trace!("fetch_access_token->payload {:?}", &payload);
-        fetch_access_token(client_id, client_secret, payload)
+        let token_info = fetch_access_token(&self.token_url, client_id, client_secret, payload);
+        if let (Some(token_info), Some(callback)) = (&token_info, &self.on_token_refresh) {
+            (callback.0)(token_info)?;
+        }
+        Ok(token_info)
     }
     /// Parse the response code in the given response url
     pub fn parse_response_code(&self, url: &mut str) -> Option<String> {
@@ -378,7 +524,10 @@ impl SpotifyOAuth {
     }
 
     /// refresh token without caching token.
-    pub fn refresh_access_token_without_cache(&self, refresh_token: &str) -> Option<TokenInfo> {
+    pub fn refresh_access_token_without_cache(
+        &self,
+        refresh_token: &str,
+    ) -> Result<Option<TokenInfo>, failure::Error> {
         let mut payload = HashMap::new();
         payload.insert("refresh_token", refresh_token);
         payload.insert("grant_type", "refresh_token");
@@ -387,12 +536,15 @@ impl SpotifyOAuth {
 
     /// after refresh access_token, the response may be empty
     /// when refresh_token again
-    pub fn refresh_access_token(&self, refresh_token: &str) -> Option<TokenInfo> {
-        if let Some(token_info) = self.refresh_access_token_without_cache(refresh_token) {
+    pub fn refresh_access_token(
+        &self,
+        refresh_token: &str,
+    ) -> Result<Option<TokenInfo>, failure::Error> {
+        if let Some(token_info) = self.refresh_access_token_without_cache(refresh_token)? {
             match serde_json::to_string(&token_info) {
                 Ok(token_info_string) => {
                     self.save_token_info(&token_info_string);
-                    Some(token_info)
+                    Ok(Some(token_info))
                 }
                 Err(why) => {
                     panic!(
@@ -402,7 +554,7 @@ impl SpotifyOAuth {
                 }
             }
         } else {
-            None
+            Ok(None)
         }
     }
     fn save_token_info(&self, token_info: &str) {
@@ -421,7 +573,13 @@ impl SpotifyOAuth {
     }
 }
 
+/// Whether a token can't be used anymore. A blank access token, e.g. from a
+/// malformed cache file, counts as expired, so that it's refreshed or
+/// obtained again instead of failing later with a 401 response.
 fn is_token_expired(token_info: &TokenInfo) -> bool {
+    if token_info.access_token.trim().is_empty() {
+        return true;
+    }
     let now: DateTime<Utc> = Utc::now();
     // 10s as buffer time
     match token_info.expires_at {
@@ -445,19 +603,38 @@ fn save_token_info(token_info: &str, path: &Path) {
         .expect("error when write file");
 }
 
+/// Request for a token: the client credentials as basic auth, the rest of
+/// the grant form-encoded, which sets its `Content-Type`. Without a client
+/// secret, as in the PKCE flow, basic auth fails, so the client ID is sent
+/// in the form instead.
+fn token_request(
+    client: &Client,
+    token_url: &str,
+    client_id: &str,
+    client_secret: &str,
+    payload: &HashMap<&str, &str>,
+) -> RequestBuilder {
+    let request = client.post(token_url);
+    if client_secret.is_empty() {
+        let mut payload = payload.clone();
+        payload.insert("client_id", client_id);
+        request.form(&payload)
+    } else {
+        request
+            .basic_auth(client_id, Some(client_secret))
+            .form(payload)
+    }
+}
+
 fn fetch_access_token(
+    token_url: &str,
     _client_id: &str,
     _client_secret: &str,
     payload: &HashMap<&str, &str>,
 ) -> Option<TokenInfo> {
+    warn_if_in_async_runtime();
     let client = Client::new();
-    let client_id = _client_id.to_owned();
-    let client_secret = _client_secret.to_owned();
-    let url = "https://accounts.spotify.com/api/token";
-    let mut response = client
-        .post(url)
-        .basic_auth(client_id, Some(client_secret))
-        .form(&payload)
+    let mut response = token_request(&client, token_url, _client_id, _client_secret, payload)
         .send()
         .expect("send request failed");
     let mut buf = String::new();
@@ -492,9 +669,129 @@ fn fetch_access_token(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
     use serde_json;
     use std::path::PathBuf;
     #[test]
+    fn test_oauth_from_lookup() {
+        let mut vars = HashMap::new();
+        vars.insert("RSPOTIFY_CLIENT_SECRET", "secret");
+        let err = SpotifyOAuth::from_lookup(|name| vars.get(name).map(|value| value.to_string()))
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "missing environment variables: RSPOTIFY_CLIENT_ID, RSPOTIFY_REDIRECT_URI"
+        );
+
+        vars.insert("RSPOTIFY_CLIENT_ID", "id");
+        vars.insert("RSPOTIFY_REDIRECT_URI", "http://localhost:8888/callback");
+        let oauth = SpotifyOAuth::from_lookup(|name| vars.get(name).map(|value| value.to_string()))
+            .unwrap();
+        assert_eq!(oauth.client_id, "id");
+        assert_eq!(oauth.client_secret, "secret");
+        assert_eq!(oauth.redirect_uri, "http://localhost:8888/callback");
+        assert_eq!(oauth.scope, "");
+
+        vars.insert(
+            "RSPOTIFY_SCOPES",
+            "user-read-private playlist-modify-public",
+        );
+        let oauth = SpotifyOAuth::from_lookup(|name| vars.get(name).map(|value| value.to_string()))
+            .unwrap();
+        assert_eq!(oauth.scope, "user-read-private playlist-modify-public");
+    }
+    /// Answers every token request with a new app token, and returns the
+    /// token URL
+    fn token_server() -> String {
+        use std::io::{Read, Write};
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let token_url = format!("http://{}/api/token", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0; 1024];
+                while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                    let n = stream.read(&mut buf).unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                }
+                let body = r#"{"access_token": "fresh-access", "token_type": "Bearer", "expires_in": 3600, "scope": ""}"#;
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                     Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+                .unwrap();
+            }
+        });
+        token_url
+    }
+
+    #[test]
+    fn test_token_fetch_runs_on_token_refresh() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let seen_by_callback = Arc::clone(&seen);
+        let mut client_credential = SpotifyClientCredentials::default()
+            .client_id("id")
+            .client_secret("secret")
+            .on_token_refresh(move |token_info| {
+                seen_by_callback
+                    .lock()
+                    .unwrap()
+                    .push(token_info.access_token.clone());
+                Ok(())
+            })
+            .build();
+        client_credential.token_url = token_server();
+        assert_eq!(
+            client_credential.get_access_token().unwrap(),
+            "fresh-access"
+        );
+        assert_eq!(*seen.lock().unwrap(), vec!["fresh-access".to_owned()]);
+
+        // a failed persist is returned, and the token isn't used
+        let mut oauth = SpotifyOAuth::default()
+            .client_id("id")
+            .client_secret("secret")
+            .on_token_refresh(|_| Err(format_err!("couldn't persist the token")))
+            .build();
+        oauth.token_url = token_server();
+        let err = oauth
+            .refresh_access_token_without_cache("refresh-token")
+            .unwrap_err();
+        assert_eq!(err.to_string(), "couldn't persist the token");
+    }
+
+    #[test]
+    fn test_token_request_is_form_encoded() {
+        let mut payload = HashMap::new();
+        payload.insert("grant_type", "client_credentials");
+        let request = token_request(&Client::new(), TOKEN_URL, "id", "secret", &payload)
+            .build()
+            .unwrap();
+        assert_eq!(
+            request.headers()[CONTENT_TYPE],
+            "application/x-www-form-urlencoded"
+        );
+        assert!(request.headers().contains_key(AUTHORIZATION));
+    }
+    #[test]
+    fn test_token_request_without_secret() {
+        let mut payload = HashMap::new();
+        payload.insert("grant_type", "authorization_code");
+        let request = token_request(&Client::new(), TOKEN_URL, "id", "", &payload)
+            .build()
+            .unwrap();
+        assert!(!request.headers().contains_key(AUTHORIZATION));
+        let body = request.body().and_then(|body| body.as_bytes()).unwrap();
+        let form: HashMap<String, String> =
+            url::form_urlencoded::parse(body).into_owned().collect();
+        assert_eq!(form["client_id"], "id");
+        assert_eq!(form["grant_type"], "authorization_code");
+    }
+    #[test]
     fn test_is_scope_subset() {
         let mut needle_scope = String::from("1 2 3");
         let mut haystack_scope = String::from("1 2 3 4");
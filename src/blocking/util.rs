@@ -4,13 +4,59 @@ use rand::distributions::Alphanumeric;
 use rand::{self, Rng};
 use webbrowser;
 
+use serde::Serialize;
 use std::collections::HashMap;
 use std::fmt::Debug;
 use std::hash::Hash;
-use std::io;
+use std::io::{self, Write};
 use std::string::ToString;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use super::oauth2::{SpotifyOAuth, TokenInfo};
+use crate::util::write_ndjson;
+
+/// Write every item of `items` to `writer` with `write_ndjson`, e.g. the
+/// pages of an endpoint, so that only the items not written yet are held.
+/// Tells how many items were written, or stops at the first error of the
+/// items or the writer, which isn't flushed.
+pub fn export_ndjson<T, I, W>(items: I, writer: &mut W) -> Result<usize, failure::Error>
+where
+    T: Serialize,
+    I: IntoIterator<Item = Result<T, failure::Error>>,
+    W: Write,
+{
+    let mut written = 0;
+    for item in items {
+        write_ndjson(writer, &item?)?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+/// Whether the current thread is running inside a tokio runtime.
+pub(crate) fn in_async_runtime() -> bool {
+    tokio::runtime::Handle::try_current().is_ok()
+}
+
+/// Whether `warn_if_in_async_runtime` already warned
+static WARNED_IN_ASYNC_RUNTIME: AtomicBool = AtomicBool::new(false);
+
+/// The blocking client stalls the executor when it's called from async code,
+/// and reqwest may then panic with an unrelated message, so say what's going
+/// on, once per process. A call from `spawn_blocking` is fine but looks the
+/// same from here, so it mustn't warn on every request. Returns whether it
+/// warned.
+pub(crate) fn warn_if_in_async_runtime() -> bool {
+    let warn = in_async_runtime() && !WARNED_IN_ASYNC_RUNTIME.swap(true, Ordering::Relaxed);
+    if warn {
+        warn!(
+            "the blocking client is called from within an async runtime, \
+             which blocks the executor; use `rspotify::client::Spotify` instead \
+             or move the call into `tokio::task::spawn_blocking`"
+        );
+    }
+    warn
+}
 
 /// convert datetime to unix timestampe
 pub fn datetime_to_timestamp(elapsed: u32) -> i64 {
@@ -71,6 +117,15 @@ pub fn convert_str_to_map(query_str: &mut str) -> HashMap<&str, &str> {
     map
 }
 
+/// The interactive helpers below only tell whether a token was obtained, so
+/// errors, e.g. from `on_token_refresh`, are logged
+fn log_token_error(token_info: Result<Option<TokenInfo>, failure::Error>) -> Option<TokenInfo> {
+    token_info.unwrap_or_else(|err| {
+        error!("couldn't get the token: {}", err);
+        None
+    })
+}
+
 pub fn request_token(spotify_oauth: &mut SpotifyOAuth) {
     let state = generate_random_string(16);
     let auth_url = spotify_oauth.get_authorize_url(Some(&state), None);
@@ -82,7 +137,7 @@ pub fn request_token(spotify_oauth: &mut SpotifyOAuth) {
 
 pub fn process_token(spotify_oauth: &mut SpotifyOAuth, input: &mut String) -> Option<TokenInfo> {
     match spotify_oauth.parse_response_code(input) {
-        Some(code) => spotify_oauth.get_access_token(&code),
+        Some(code) => log_token_error(spotify_oauth.get_access_token(&code)),
         None => None,
     }
 }
@@ -92,14 +147,14 @@ pub fn process_token_without_cache(
     input: &mut String,
 ) -> Option<TokenInfo> {
     match spotify_oauth.parse_response_code(input) {
-        Some(code) => spotify_oauth.get_access_token_without_cache(&code),
+        Some(code) => log_token_error(spotify_oauth.get_access_token_without_cache(&code)),
         None => None,
     }
 }
 
 /// get tokenInfo by Authorization
 pub fn get_token(spotify_oauth: &mut SpotifyOAuth) -> Option<TokenInfo> {
-    match spotify_oauth.get_cached_token() {
+    match log_token_error(spotify_oauth.get_cached_token()) {
         Some(token_info) => Some(token_info),
         None => {
             request_token(spotify_oauth);
@@ -126,13 +181,42 @@ pub fn get_token_without_cache(spotify_oauth: &mut SpotifyOAuth) -> Option<Token
 
 /// get tokenInfo by authorization and code
 pub fn get_token_by_code(spotify_oauth: &mut SpotifyOAuth, code: &str) -> Option<TokenInfo> {
-    spotify_oauth.get_access_token(&code)
+    log_token_error(spotify_oauth.get_access_token(&code))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     #[test]
+    fn test_export_ndjson() {
+        let mut out = Vec::new();
+        assert_eq!(export_ndjson(vec![Ok(1), Ok(2)], &mut out).unwrap(), 2);
+        assert_eq!(out, b"1\n2\n");
+        // the items after an error aren't written
+        let items = vec![Ok(1), Err(format_err!("page")), Ok(3)];
+        let mut out = Vec::new();
+        assert!(export_ndjson(items, &mut out).is_err());
+        assert_eq!(out, b"1\n");
+    }
+    #[test]
+    fn test_in_async_runtime() {
+        assert!(!in_async_runtime());
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        assert!(runtime.block_on(async { in_async_runtime() }));
+    }
+    #[test]
+    fn test_warn_once_from_spawn_blocking() {
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        let warned = runtime.block_on(async {
+            tokio::task::spawn_blocking(|| (0..3).filter(|_| warn_if_in_async_runtime()).count())
+                .await
+                .unwrap()
+        });
+        // no other test calls the guard from within a runtime
+        assert_eq!(warned, 1);
+        assert!(!warn_if_in_async_runtime());
+    }
+    #[test]
     fn test_covert_str_to_map() {
         let mut query_url = String::from("redirect_uri=my_uri&state=my-state&scope=test-scope&");
         let parameters = convert_str_to_map(&mut query_url);
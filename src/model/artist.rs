@@ -1,7 +1,9 @@
 //! All objects related to artist defined by Spotify API
 
+use super::album::SimplifiedAlbum;
 use super::image::Image;
-use super::page::CursorBasedPage;
+use super::page::{CursorBasedPage, Page};
+use super::track::FullTrack;
 use crate::senum::Type;
 use serde_json::Value;
 use std::collections::HashMap;
@@ -46,3 +48,14 @@ pub struct FullArtists {
 pub struct CursorPageFullArtists {
     pub artists: CursorBasedPage<FullArtist>,
 }
+
+/// Everything an artist page needs, see `Spotify::artist_page`. The top
+/// tracks, albums and related artists are `None` when their request failed.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ArtistPage {
+    pub artist: FullArtist,
+    pub top_tracks: Option<Vec<FullTrack>>,
+    /// The first page of the artist's albums
+    pub albums: Option<Page<SimplifiedAlbum>>,
+    pub related_artists: Option<Vec<FullArtist>>,
+}
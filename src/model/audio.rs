@@ -25,6 +25,46 @@ pub struct AudioFeatures {
     pub valence: f32,
 }
 
+/// A numeric attribute of `AudioFeatures`, e.g. to sort tracks by, see
+/// `Spotify::sort_tracks_by_feature`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AudioFeature {
+    Acousticness,
+    Danceability,
+    DurationMs,
+    Energy,
+    Instrumentalness,
+    Key,
+    Liveness,
+    Loudness,
+    Mode,
+    Speechiness,
+    Tempo,
+    TimeSignature,
+    Valence,
+}
+
+impl AudioFeatures {
+    /// The value of `feature` for this track
+    pub fn value(&self, feature: AudioFeature) -> f32 {
+        match feature {
+            AudioFeature::Acousticness => self.acousticness,
+            AudioFeature::Danceability => self.danceability,
+            AudioFeature::DurationMs => self.duration_ms as f32,
+            AudioFeature::Energy => self.energy,
+            AudioFeature::Instrumentalness => self.instrumentalness,
+            AudioFeature::Key => self.key as f32,
+            AudioFeature::Liveness => self.liveness,
+            AudioFeature::Loudness => self.loudness,
+            AudioFeature::Mode => self.mode,
+            AudioFeature::Speechiness => self.speechiness,
+            AudioFeature::Tempo => self.tempo,
+            AudioFeature::TimeSignature => self.time_signature as f32,
+            AudioFeature::Valence => self.valence,
+        }
+    }
+}
+
 /// Audio Feature Vector
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct AudioFeaturesPayload {
@@ -124,3 +164,51 @@ pub struct AudioAnalysisTrack {
     pub rhythmstring: String,
     pub rhythm_version: f32,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FEATURES: &str = r#"{
+        "acousticness": 0.00242,
+        "analysis_url": "https://api.spotify.com/v1/audio-analysis/06AKEBrKUckW0KREUWRnvT",
+        "danceability": 0.585,
+        "duration_ms": 255349,
+        "energy": 0.842,
+        "id": "06AKEBrKUckW0KREUWRnvT",
+        "instrumentalness": 0.00686,
+        "key": 9,
+        "liveness": 0.0866,
+        "loudness": -5.883,
+        "mode": 0,
+        "speechiness": 0.0556,
+        "tempo": 118.211,
+        "time_signature": 4,
+        "track_href": "https://api.spotify.com/v1/tracks/06AKEBrKUckW0KREUWRnvT",
+        "type": "audio_features",
+        "uri": "spotify:track:06AKEBrKUckW0KREUWRnvT",
+        "valence": 0.428
+    }"#;
+
+    #[test]
+    fn test_audio_features_response_shapes() {
+        // `audio-features/{id}` answers with the object itself
+        let features: AudioFeatures = serde_json::from_str(FEATURES).unwrap();
+        assert_eq!(features.id, "06AKEBrKUckW0KREUWRnvT");
+        assert!(serde_json::from_str::<AudioFeaturesPayload>(FEATURES).is_err());
+
+        // `audio-features?ids=` wraps the objects in a list
+        let payload = format!(r#"{{"audio_features": [{}]}}"#, FEATURES);
+        let payload: AudioFeaturesPayload = serde_json::from_str(&payload).unwrap();
+        assert_eq!(payload.audio_features.len(), 1);
+        assert!(serde_json::from_str::<AudioFeatures>(&format!("[{}]", FEATURES)).is_err());
+    }
+
+    #[test]
+    fn test_audio_feature_value() {
+        let features: AudioFeatures = serde_json::from_str(FEATURES).unwrap();
+        assert_eq!(features.value(AudioFeature::Tempo), 118.211);
+        assert_eq!(features.value(AudioFeature::Key), 9.0);
+        assert_eq!(features.value(AudioFeature::DurationMs), 255349.0);
+    }
+}
@@ -1,5 +1,6 @@
 //! All objects related to context
 use std::collections::HashMap;
+use std::time::Duration;
 
 use super::device::Device;
 use super::track::FullTrack;
@@ -41,6 +42,10 @@ pub struct SimplifiedPlayingContext {
 }
 
 /// [Currently playing object](https://developer.spotify.com/documentation/web-api/reference/player/get-the-users-currently-playing-track/)
+///
+///What `Spotify::current_playing` returns: the lighter sibling of
+///`CurrentlyPlaybackContext`, without the device, repeat and shuffle state.
+///A playback context converts into it with `From`.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct CurrentlyPlayingContext {
     pub context: Option<Context>,
@@ -51,7 +56,12 @@ pub struct CurrentlyPlayingContext {
     pub currently_playing_type: CurrentlyPlayingType,
     pub actions: Actions,
 }
+
 /// [Currently Playback Context](https://developer.spotify.com/documentation/web-api/reference/player/get-information-about-the-users-current-playback/)
+///
+///What `Spotify::current_playback` returns: every field of
+///`CurrentlyPlayingContext`, plus the device playing and its repeat and
+///shuffle state.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct CurrentlyPlaybackContext {
     pub device: Device,
@@ -66,8 +76,168 @@ pub struct CurrentlyPlaybackContext {
     pub actions: Actions,
 }
 
+impl From<CurrentlyPlaybackContext> for CurrentlyPlayingContext {
+    fn from(playback: CurrentlyPlaybackContext) -> CurrentlyPlayingContext {
+        CurrentlyPlayingContext {
+            context: playback.context,
+            timestamp: playback.timestamp,
+            progress_ms: playback.progress_ms,
+            is_playing: playback.is_playing,
+            item: playback.item,
+            currently_playing_type: playback.currently_playing_type,
+            actions: playback.actions,
+        }
+    }
+}
+
+/// Playback of an item, from `current_playing` or `current_playback`
+pub trait PlaybackProgress {
+    /// How far into the item playback is, in milliseconds
+    fn progress_ms(&self) -> Option<u32>;
+
+    /// The item playing
+    fn item(&self) -> Option<&PlayingItem>;
+
+    /// How far into the item playback is, `None` when nothing is playing
+    fn progress(&self) -> Option<Duration> {
+        self.progress_ms()
+            .map(|progress_ms| Duration::from_millis(u64::from(progress_ms)))
+    }
+
+    /// How far into the item playback is, from 0 to 1, for a progress bar.
+    /// `None` when nothing is playing, or when the item has no duration.
+    fn progress_fraction(&self) -> Option<f64> {
+        let duration_ms = self.item()?.duration_ms();
+        if duration_ms == 0 {
+            return None;
+        }
+        let fraction = f64::from(self.progress_ms()?) / f64::from(duration_ms);
+        Some(fraction.min(1.0))
+    }
+}
+
+macro_rules! playback_progress {
+    ($($model:ty),*) => {
+        $(impl PlaybackProgress for $model {
+            fn progress_ms(&self) -> Option<u32> {
+                self.progress_ms
+            }
+
+            fn item(&self) -> Option<&PlayingItem> {
+                self.item.as_ref()
+            }
+        })*
+    };
+}
+
+playback_progress!(CurrentlyPlayingContext, CurrentlyPlaybackContext);
+
+/// [Queue object](https://developer.spotify.com/documentation/web-api/reference/#endpoint-get-queue)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CurrentUserQueue {
+    pub currently_playing: Option<PlayingItem>,
+    pub queue: Vec<PlayingItem>,
+}
+
+/// Everything a "now playing" view needs, see `Spotify::playback_snapshot`.
+/// `devices` and `queue` are `None` when their request failed, or when the
+/// queue wasn't asked for.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlaybackSnapshot {
+    pub playback: Option<CurrentlyPlaybackContext>,
+    pub devices: Option<Vec<Device>>,
+    pub queue: Option<CurrentUserQueue>,
+}
+
 /// [actions](https://developer.spotify.com/documentation/web-api/reference/player/get-the-users-currently-playing-track/)
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Actions {
     pub disallows: HashMap<DisallowKey, bool>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn playing_context(progress_ms: Option<u32>, item: bool) -> CurrentlyPlayingContext {
+        let track = serde_json::json!({
+            "album": {
+                "album_type": "album",
+                "artists": [],
+                "external_urls": {},
+                "href": null,
+                "id": null,
+                "images": [],
+                "name": "Dolor",
+                "type": "album",
+                "uri": null
+            },
+            "artists": [],
+            "disc_number": 1,
+            "duration_ms": 200000,
+            "explicit": false,
+            "external_ids": {},
+            "external_urls": {},
+            "href": null,
+            "id": "6rqhFgbbKwnb9MLmUQDhG6",
+            "is_local": false,
+            "name": "Speak Now",
+            "popularity": 0,
+            "preview_url": null,
+            "track_number": 1,
+            "type": "track",
+            "uri": "spotify:track:6rqhFgbbKwnb9MLmUQDhG6"
+        });
+        serde_json::from_value(serde_json::json!({
+            "context": null,
+            "timestamp": 1490252122574u64,
+            "progress_ms": progress_ms,
+            "is_playing": true,
+            "item": if item { track } else { serde_json::Value::Null },
+            "currently_playing_type": "track",
+            "actions": {"disallows": {}}
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_progress() {
+        let context = playing_context(Some(50000), true);
+        assert!(context.is_playing);
+        assert_eq!(context.progress(), Some(Duration::from_secs(50)));
+        assert_eq!(context.progress_fraction(), Some(0.25));
+        // progress is reported slightly past the end at times
+        let context = playing_context(Some(200500), true);
+        assert_eq!(context.progress_fraction(), Some(1.0));
+
+        let context = playing_context(None, true);
+        assert_eq!(context.progress(), None);
+        assert_eq!(context.progress_fraction(), None);
+        let context = playing_context(Some(50000), false);
+        assert_eq!(context.progress_fraction(), None);
+    }
+
+    #[test]
+    fn test_playback_into_playing() {
+        let playing = playing_context(Some(50000), true);
+        let mut playback = serde_json::to_value(&playing).unwrap();
+        playback["device"] = serde_json::json!({
+            "id": "5fbb3ba6aa454b5534c4ba43a8c7e8e45a63ad0e",
+            "is_active": true,
+            "is_private_session": false,
+            "is_restricted": false,
+            "name": "My fridge",
+            "type": "Computer",
+            "volume_percent": 100
+        });
+        playback["repeat_state"] = "off".into();
+        playback["shuffle_state"] = false.into();
+        let playback: CurrentlyPlaybackContext = serde_json::from_value(playback).unwrap();
+        assert_eq!(playback.device.name, "My fridge");
+        let from_playback = CurrentlyPlayingContext::from(playback);
+        assert_eq!(
+            serde_json::to_value(&from_playback).unwrap(),
+            serde_json::to_value(&playing).unwrap()
+        );
+    }
+}
@@ -16,3 +16,51 @@ pub struct Device {
 pub struct DevicePayload {
     pub devices: Vec<Device>,
 }
+
+impl DevicePayload {
+    /// The device playback should default to. Spotify doesn't report when a
+    /// device was last used, so the selection is:
+    /// 1. the currently active device, if any;
+    /// 2. otherwise the first device that accepts Web API commands
+    ///    (`is_restricted` is false), in the order Spotify returned them.
+    pub fn preferred(&self) -> Option<&Device> {
+        self.devices
+            .iter()
+            .find(|device| device.is_active)
+            .or_else(|| self.devices.iter().find(|device| !device.is_restricted))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload(devices: &[(&str, bool, bool)]) -> DevicePayload {
+        DevicePayload {
+            devices: devices
+                .iter()
+                .map(|&(id, is_active, is_restricted)| Device {
+                    id: id.to_owned(),
+                    is_active,
+                    is_restricted,
+                    name: id.to_owned(),
+                    _type: DeviceType::Computer,
+                    volume_percent: 50,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_preferred_device() {
+        let devices = payload(&[("a", false, false), ("b", true, false)]);
+        assert_eq!(devices.preferred().unwrap().id, "b");
+
+        let devices = payload(&[("a", false, true), ("b", false, false)]);
+        assert_eq!(devices.preferred().unwrap().id, "b");
+
+        let devices = payload(&[("a", false, true)]);
+        assert!(devices.preferred().is_none());
+        assert!(payload(&[]).preferred().is_none());
+    }
+}
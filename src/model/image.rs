@@ -1,4 +1,11 @@
 //! Image object
+use super::album::{FullAlbum, SimplifiedAlbum};
+use super::artist::FullArtist;
+use super::category::Category;
+use super::playlist::{FullPlaylist, SimplifiedPlaylist};
+use super::show::{FullEpisode, FullShow, SimplifiedEpisode, SimplifiedShow};
+use super::user::{PrivateUser, PublicUser};
+
 ///[image object](https://developer.spotify.com/web-api/object-model/#image-object)
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Image {
@@ -6,3 +13,107 @@ pub struct Image {
     pub url: String,
     pub width: Option<u32>,
 }
+
+/// The image of `images` whose width is closest to `width`, the larger one
+/// on a tie, as scaling down looks better than scaling up. Images of unknown
+/// width are only picked when no width is known at all, the first one then.
+pub fn closest_image(images: &[Image], width: u32) -> Option<&Image> {
+    images
+        .iter()
+        .filter_map(|image| image.width.map(|image_width| (image, image_width)))
+        .min_by_key(|&(_, image_width)| {
+            let distance = (i64::from(image_width) - i64::from(width)).abs();
+            (distance, u32::MAX - image_width)
+        })
+        .map(|(image, _)| image)
+        .or_else(|| images.first())
+}
+
+/// Objects Spotify serves images of, in several sizes: album and show covers,
+/// artist and user pictures...
+pub trait HasImages {
+    /// All the images, in any size
+    fn images(&self) -> &[Image];
+
+    /// The image whose width is closest to `width`, see `closest_image`
+    fn image_closest_to(&self, width: u32) -> Option<&Image> {
+        closest_image(self.images(), width)
+    }
+}
+
+macro_rules! has_images {
+    ($($model:ty),*) => {
+        $(impl HasImages for $model {
+            fn images(&self) -> &[Image] {
+                &self.images
+            }
+        })*
+    };
+}
+
+has_images!(
+    SimplifiedAlbum,
+    FullAlbum,
+    FullArtist,
+    SimplifiedPlaylist,
+    FullPlaylist,
+    SimplifiedShow,
+    FullShow,
+    SimplifiedEpisode,
+    FullEpisode
+);
+
+impl HasImages for PublicUser {
+    fn images(&self) -> &[Image] {
+        self.images.as_deref().unwrap_or(&[])
+    }
+}
+
+impl HasImages for PrivateUser {
+    fn images(&self) -> &[Image] {
+        self.images.as_deref().unwrap_or(&[])
+    }
+}
+
+impl HasImages for Category {
+    fn images(&self) -> &[Image] {
+        &self.icons
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(width: Option<u32>) -> Image {
+        Image {
+            height: width,
+            url: format!("https://i.scdn.co/image/{:?}", width),
+            width,
+        }
+    }
+
+    #[test]
+    fn test_closest_image() {
+        let images = vec![image(Some(640)), image(Some(300)), image(Some(64))];
+        assert_eq!(closest_image(&images, 50).unwrap().width, Some(64));
+        assert_eq!(closest_image(&images, 400).unwrap().width, Some(300));
+        assert_eq!(closest_image(&images, 1000).unwrap().width, Some(640));
+        // equally far from 100 and 200, the larger one is picked
+        let tie = vec![image(Some(100)), image(Some(200))];
+        assert_eq!(closest_image(&tie, 150).unwrap().width, Some(200));
+        let unknown = vec![image(None), image(Some(300))];
+        assert_eq!(closest_image(&unknown, 50).unwrap().width, Some(300));
+        assert!(closest_image(&[image(None)], 50).is_some());
+        assert!(closest_image(&[], 50).is_none());
+    }
+
+    #[test]
+    fn test_user_without_images() {
+        let user: PublicUser = serde_json::from_str(
+            r#"{"external_urls": {}, "href": "", "id": "wizzler", "type": "user", "uri": ""}"#,
+        )
+        .unwrap();
+        assert!(user.image_closest_to(64).is_none());
+    }
+}
@@ -23,3 +23,13 @@ pub enum PlayingItem {
     Track(track::FullTrack),
     Episode(show::FullEpisode),
 }
+
+impl PlayingItem {
+    /// Duration of the track or episode in milliseconds
+    pub fn duration_ms(&self) -> u32 {
+        match *self {
+            PlayingItem::Track(ref track) => track.duration_ms,
+            PlayingItem::Episode(ref episode) => episode.duration_ms,
+        }
+    }
+}
@@ -1,16 +1,54 @@
 //! All kinds of page object
 ///Basic page
 ///ppaging abject(https://developer.spotify.com/web-api/object-model/#paging-object)
+///
+///Besides `items`, the paging fields are public so callers can build
+///pagination controls on top of any paged endpoint.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Page<T> {
     pub href: String,
     pub items: Vec<T>,
+    ///the maximum number of items requested for this page
     pub limit: u32,
+    ///URL of the next page, `None` on the last page
     pub next: Option<String>,
+    ///index of the first item of this page within the whole collection
     pub offset: u32,
+    ///URL of the previous page, `None` on the first page
     pub previous: Option<String>,
+    ///the total number of items available across all pages
     pub total: u32,
 }
+
+impl<T> Page<T> {
+    ///whether there are more items after this page
+    pub fn has_next(&self) -> bool {
+        self.next.is_some()
+    }
+
+    ///whether there are items before this page
+    pub fn has_previous(&self) -> bool {
+        self.previous.is_some()
+    }
+
+    ///append the items of the next page. `offset` and `previous` still
+    ///describe where the items start, the other paging fields are taken from
+    ///the latest page.
+    pub fn append(&mut self, next: Page<T>) {
+        self.items.extend(next.items);
+        self.href = next.href;
+        self.limit = next.limit;
+        self.next = next.next;
+        self.total = next.total;
+    }
+
+    ///offset to request the next page with, if there is one
+    pub fn next_offset(&self) -> Option<u32> {
+        self.next
+            .as_ref()
+            .map(|_| self.offset + self.items.len() as u32)
+    }
+}
 /// cursor based page
 ///[cursor based paging object](https://developer.spotify.com/web-api/object-model/#cursor-based-paging-object)
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -30,3 +68,44 @@ pub struct CursorBasedPage<T> {
 pub struct Cursor {
     pub after: Option<String>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    #[test]
+    fn test_page_paging_fields() {
+        let json = r#"{
+            "href": "https://api.spotify.com/v1/me/playlists?offset=20&limit=20",
+            "items": [1, 2, 3],
+            "limit": 20,
+            "next": "https://api.spotify.com/v1/me/playlists?offset=23&limit=20",
+            "offset": 20,
+            "previous": "https://api.spotify.com/v1/me/playlists?offset=0&limit=20",
+            "total": 45
+        }"#;
+        let page: Page<u32> = serde_json::from_str(json).unwrap();
+        assert_eq!(page.total, 45);
+        assert_eq!(page.limit, 20);
+        assert_eq!(page.offset, 20);
+        assert!(page.has_next());
+        assert!(page.has_previous());
+        assert_eq!(page.next_offset(), Some(23));
+
+        // The paging fields survive serialization, e.g. when a webapp
+        // forwards the page as JSON to its frontend
+        let value = serde_json::to_value(&page).unwrap();
+        assert_eq!(value["total"], Value::from(45));
+        assert_eq!(value["offset"], Value::from(20));
+        assert!(value["next"].is_string());
+
+        let last: Page<u32> = serde_json::from_value(serde_json::json!({
+            "href": "", "items": [], "limit": 20, "next": null,
+            "offset": 40, "previous": null, "total": 40
+        }))
+        .unwrap();
+        assert!(!last.has_next());
+        assert_eq!(last.next_offset(), None);
+    }
+}
@@ -22,3 +22,40 @@ pub struct PlayHistory {
     pub played_at: DateTime<Utc>,
     pub context: Option<Context>,
 }
+
+/// Time window of `Spotify::current_user_recently_played`: only plays
+/// strictly before or after a point in time. Spotify doesn't accept both at
+/// once.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeLimits {
+    Before(DateTime<Utc>),
+    After(DateTime<Utc>),
+}
+
+impl TimeLimits {
+    /// The query parameter and its value, a Unix timestamp in milliseconds
+    pub fn param(&self) -> (&'static str, String) {
+        match *self {
+            TimeLimits::Before(before) => ("before", before.timestamp_millis().to_string()),
+            TimeLimits::After(after) => ("after", after.timestamp_millis().to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time_limits_param() {
+        let date: DateTime<Utc> = "2020-03-07T10:15:30.250Z".parse().unwrap();
+        assert_eq!(
+            TimeLimits::Before(date).param(),
+            ("before", "1583576130250".to_owned())
+        );
+        assert_eq!(
+            TimeLimits::After(date).param(),
+            ("after", "1583576130250".to_owned())
+        );
+    }
+}
@@ -46,16 +46,135 @@ pub struct FullPlaylist {
 }
 
 ///[playlist track object](https://developer.spotify.com/web-api/object-model/#playlist-track-object)
+///When fetched with a `market`, `track` is relinked the same way as a direct
+///track lookup, so `is_playable` and `linked_from` are kept.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PlaylistTrack {
     pub added_at: DateTime<Utc>,
+    /// Who added the track, `None` in very old playlists, where Spotify
+    /// sends `null`, and when that user was deleted
+    #[serde(default, deserialize_with = "deserialize_added_by")]
     pub added_by: Option<PublicUser>,
     pub is_local: bool,
     pub track: Option<FullTrack>,
 }
+/// `added_by` of a playlist track. It's `None` for `null` and for a deleted
+/// user, which comes with an empty or no ID. A user with an ID but without
+/// the other fields, e.g. `{"id": "wizzler"}`, is kept, with the missing
+/// fields derived from the ID; anything else that isn't a user is an error.
+fn deserialize_added_by<'de, D>(deserializer: D) -> Result<Option<PublicUser>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let mut user = match <Option<Value> as serde::Deserialize>::deserialize(deserializer)? {
+        Some(Value::Object(user)) => user,
+        Some(Value::Null) | None => return Ok(None),
+        Some(other) => {
+            return Err(serde::de::Error::custom(format!(
+                "invalid added_by user: {}",
+                other
+            )))
+        }
+    };
+    let id = match user.get("id") {
+        None | Some(Value::Null) => return Ok(None),
+        Some(Value::String(id)) if id.is_empty() => return Ok(None),
+        Some(Value::String(id)) => id.to_owned(),
+        Some(other) => {
+            return Err(serde::de::Error::custom(format!(
+                "invalid added_by user id: {}",
+                other
+            )))
+        }
+    };
+    user.entry("external_urls")
+        .or_insert_with(|| Value::Object(Default::default()));
+    user.entry("href")
+        .or_insert_with(|| format!("https://api.spotify.com/v1/users/{}", id).into());
+    user.entry("type").or_insert_with(|| "user".into());
+    user.entry("uri")
+        .or_insert_with(|| format!("spotify:user:{}", id).into());
+    serde_json::from_value(Value::Object(user))
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+/// All the tracks of a playlist and the snapshot id, i.e. the version of the
+/// playlist, they were read at. Spotify has no way to list only the changes
+/// to a playlist, but a copy can be kept in sync incrementally:
+///
+/// - keep the `snapshot_id` of the last sync, and skip the playlist while
+///   `Spotify::playlist_changed_since` says it didn't change;
+/// - otherwise fetch it again and compare the tracks by position and
+///   `added_at`: tracks added after the last sync are new, the others were
+///   moved or removed.
+///
+/// The snapshot id is read with the first page of tracks, so it may be older
+/// than the last pages if the playlist is edited while they are fetched.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlaylistTracksSnapshot {
+    pub snapshot_id: String,
+    pub tracks: Vec<PlaylistTrack>,
+}
 ///[get list featured playlists](https://developer.spotify.com/web-api/get-list-featured-playlists/)
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct FeaturedPlaylists {
     pub message: String,
     pub playlists: Page<SimplifiedPlaylist>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_playlist_track_missing_added_by() {
+        let track = |added_by: Value| -> PlaylistTrack {
+            let mut json = serde_json::json!({
+                "added_at": "2008-11-02T10:00:00Z",
+                "is_local": false,
+                "track": null
+            });
+            json["added_by"] = added_by;
+            serde_json::from_value(json).unwrap()
+        };
+        assert!(track(Value::Null).added_by.is_none());
+        // a deleted user
+        let deleted = serde_json::json!({
+            "external_urls": {},
+            "href": "https://api.spotify.com/v1/users/",
+            "id": "",
+            "type": "user",
+            "uri": "spotify:user:"
+        });
+        assert!(track(deleted).added_by.is_none());
+        assert!(track(serde_json::json!({})).added_by.is_none());
+        // a partial user keeps its ID
+        let partial = track(serde_json::json!({"id": "wizzler"}))
+            .added_by
+            .unwrap();
+        assert_eq!(partial.id, "wizzler");
+        assert_eq!(partial.uri, "spotify:user:wizzler");
+        // what isn't a user is an error, not a missing user
+        let mut json = serde_json::json!({
+            "added_at": "2008-11-02T10:00:00Z",
+            "added_by": {"id": 42},
+            "is_local": false,
+            "track": null
+        });
+        assert!(serde_json::from_value::<PlaylistTrack>(json.clone()).is_err());
+        json["added_by"] = serde_json::json!({"id": "wizzler", "external_urls": []});
+        assert!(serde_json::from_value::<PlaylistTrack>(json).is_err());
+        let user = serde_json::json!({
+            "external_urls": {},
+            "href": "https://api.spotify.com/v1/users/wizzler",
+            "id": "wizzler",
+            "type": "user",
+            "uri": "spotify:user:wizzler"
+        });
+        assert_eq!(track(user).added_by.unwrap().id, "wizzler");
+        // left out altogether
+        let json = r#"{"added_at": "2008-11-02T10:00:00Z", "is_local": false, "track": null}"#;
+        let missing: PlaylistTrack = serde_json::from_str(json).unwrap();
+        assert!(missing.added_by.is_none());
+    }
+}
@@ -1,11 +1,23 @@
 //! All objects related to recommendation
 use super::track::SimplifiedTrack;
+use crate::client::ClientError;
+use crate::senum::Type;
+use std::collections::{HashSet, VecDeque};
 ///[recommendations object](https://developer.spotify.com/web-api/object-model/#recommendations-object)
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Recommendations {
     pub seeds: Vec<RecommendationsSeed>,
     pub tracks: Vec<SimplifiedTrack>,
 }
+
+impl Recommendations {
+    /// Drop the tracks that can't be played in the market the
+    /// recommendations were requested for. Without a market Spotify doesn't
+    /// tell, and every track is kept.
+    pub fn retain_playable(&mut self) {
+        self.tracks.retain(|track| track.is_playable != Some(false));
+    }
+}
 ///[recommendations seed object](https://developer.spotify.com/web-api/object-model/#recommendations-seed-object)
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct RecommendationsSeed {
@@ -30,3 +42,262 @@ pub enum RecommendationsSeedType {
     #[serde(rename = "GENRE")]
     Genre,
 }
+
+/// Spotify takes at most this many seeds, artists, genres and tracks together
+pub const MAX_SEEDS: usize = 5;
+
+/// Seeds of a recommendations request built from a mixed list: artist and
+/// track URIs or URLs are routed to `seed_artists` and `seed_tracks`, genre
+/// names to `seed_genres`. See `Spotify::recommendations_with_seeds`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RecommendationSeeds {
+    pub artists: Vec<String>,
+    pub genres: Vec<String>,
+    pub tracks: Vec<String>,
+}
+
+impl RecommendationSeeds {
+    /// Add an artist or track, given as a URI (`spotify:artist:...`) or URL
+    /// (`https://open.spotify.com/track/...`). Bare IDs are rejected, their
+    /// type can't be told apart.
+    pub fn uri(mut self, uri: &str) -> Result<RecommendationSeeds, failure::Error> {
+        let path = uri.split('?').next().unwrap_or("");
+        let mut segments = path
+            .split(&[':', '/'][..])
+            .filter(|segment| !segment.is_empty())
+            .rev();
+        let (id, _type) = match (segments.next(), segments.next()) {
+            (Some(id), Some(_type)) => (id, _type.parse::<Type>().ok()),
+            _ => bail!("not a Spotify URI or URL: {}", uri),
+        };
+        match _type {
+            Some(Type::Artist) => self.artists.push(id.to_owned()),
+            Some(Type::Track) => self.tracks.push(id.to_owned()),
+            _ => bail!("only artists and tracks can seed recommendations: {}", uri),
+        }
+        Ok(self)
+    }
+
+    /// Add a genre name, such as `rock`
+    pub fn genre(mut self, genre: &str) -> RecommendationSeeds {
+        self.genres.push(genre.to_owned());
+        self
+    }
+
+    /// Number of seeds of all kinds
+    pub fn len(&self) -> usize {
+        self.artists.len() + self.genres.len() + self.tracks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Spotify needs between 1 and `MAX_SEEDS` seeds in total, fails with
+    /// `ClientError::InvalidParameter` otherwise
+    pub fn check(&self) -> Result<(), failure::Error> {
+        let message = if self.is_empty() {
+            "recommendations need at least one seed".to_owned()
+        } else if self.len() > MAX_SEEDS {
+            format!(
+                "recommendations take at most {} seeds, got {}",
+                MAX_SEEDS,
+                self.len()
+            )
+        } else {
+            return Ok(());
+        };
+        Err(ClientError::InvalidParameter(message).into())
+    }
+}
+
+/// How many of the initial seeds of a `RecommendationRadio` are kept when it
+/// is reseeded, to stay on its theme
+pub const RADIO_ANCHOR_SEEDS: usize = 2;
+
+/// State of an endless radio built on recommendations, see
+/// `Spotify::radio_next`. It remembers every track it yielded so that none
+/// is yielded twice, and after every batch it's reseeded with the first
+/// `RADIO_ANCHOR_SEEDS` of its initial seeds, to stay on theme, and the
+/// latest tracks it yielded, for variety.
+#[derive(Clone, Debug)]
+pub struct RecommendationRadio {
+    anchor: RecommendationSeeds,
+    seeds: RecommendationSeeds,
+    /// IDs of the tracks yielded so far
+    yielded: HashSet<String>,
+    /// IDs of the latest tracks yielded, oldest first
+    latest: VecDeque<String>,
+}
+
+impl RecommendationRadio {
+    /// Start a radio from `seeds`, which must be valid recommendation seeds
+    pub fn new(seeds: RecommendationSeeds) -> Result<RecommendationRadio, failure::Error> {
+        seeds.check()?;
+        let mut room = RADIO_ANCHOR_SEEDS;
+        let mut take = |from: &Vec<String>| {
+            let taken: Vec<String> = from.iter().take(room).cloned().collect();
+            room -= taken.len();
+            taken
+        };
+        let anchor = RecommendationSeeds {
+            artists: take(&seeds.artists),
+            genres: take(&seeds.genres),
+            tracks: take(&seeds.tracks),
+        };
+        Ok(RecommendationRadio {
+            anchor,
+            seeds,
+            yielded: HashSet::new(),
+            latest: VecDeque::new(),
+        })
+    }
+
+    /// Seeds of the next batch
+    pub fn seeds(&self) -> &RecommendationSeeds {
+        &self.seeds
+    }
+
+    /// Keep the tracks of a batch that weren't yielded before, and reseed
+    /// the radio with them. Tracks without an ID are left out.
+    pub fn take_new(&mut self, tracks: Vec<SimplifiedTrack>) -> Vec<SimplifiedTrack> {
+        let yielded = &mut self.yielded;
+        let fresh: Vec<SimplifiedTrack> = tracks
+            .into_iter()
+            .filter(|track| match track.id {
+                Some(ref id) => yielded.insert(id.clone()),
+                None => false,
+            })
+            .collect();
+        let room = MAX_SEEDS - self.anchor.len();
+        for id in fresh.iter().filter_map(|track| track.id.clone()) {
+            self.latest.push_back(id);
+            if self.latest.len() > room {
+                self.latest.pop_front();
+            }
+        }
+        if !self.latest.is_empty() {
+            let mut seeds = self.anchor.clone();
+            seeds.tracks.extend(self.latest.iter().cloned());
+            self.seeds = seeds;
+        }
+        fresh
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A recommended track named after its `id`, with `is_playable` only
+    /// when given, as without a market
+    fn track_json(id: &str, playable: Option<bool>) -> serde_json::Value {
+        let mut track = serde_json::json!({
+            "artists": [],
+            "disc_number": 1,
+            "duration_ms": 207959,
+            "explicit": false,
+            "external_urls": {},
+            "id": id,
+            "is_local": false,
+            "name": id,
+            "preview_url": null,
+            "track_number": 1,
+            "type": "track",
+            "uri": format!("spotify:track:{}", id)
+        });
+        if let Some(playable) = playable {
+            track["is_playable"] = playable.into();
+        }
+        track
+    }
+
+    #[test]
+    fn test_recommendations_retain_playable() {
+        let mut recommendations: Recommendations = serde_json::from_value(serde_json::json!({
+            "seeds": [],
+            "tracks": [
+                track_json("playable", Some(true)),
+                track_json("unplayable", Some(false)),
+                track_json("unknown", None)
+            ]
+        }))
+        .unwrap();
+        recommendations.retain_playable();
+        let ids: Vec<_> = recommendations
+            .tracks
+            .iter()
+            .map(|track| track.id.as_deref().unwrap())
+            .collect();
+        assert_eq!(ids, vec!["playable", "unknown"]);
+    }
+
+    #[test]
+    fn test_recommendation_radio() {
+        let track =
+            |id: &str| -> SimplifiedTrack { serde_json::from_value(track_json(id, None)).unwrap() };
+        let seeds = RecommendationSeeds::default()
+            .genre("rock")
+            .genre("jazz")
+            .genre("soul");
+        let mut radio = RecommendationRadio::new(seeds.clone()).unwrap();
+        assert_eq!(radio.seeds(), &seeds);
+        let ids = |tracks: Vec<SimplifiedTrack>| -> Vec<String> {
+            tracks.into_iter().filter_map(|track| track.id).collect()
+        };
+        let fresh = radio.take_new(vec![track("a"), track("b"), track("a")]);
+        assert_eq!(ids(fresh), vec!["a", "b"]);
+        assert_eq!(radio.seeds().genres, vec!["rock", "jazz"]);
+        assert_eq!(radio.seeds().tracks, vec!["a", "b"]);
+        let fresh = radio.take_new(vec![track("b"), track("c"), track("d")]);
+        assert_eq!(ids(fresh), vec!["c", "d"]);
+        // the latest tracks fill the seeds left after the anchor
+        assert_eq!(radio.seeds().tracks, vec!["b", "c", "d"]);
+        assert!(radio.seeds().check().is_ok());
+        // a batch of tracks yielded before doesn't change the seeds
+        assert!(radio.take_new(vec![track("a")]).is_empty());
+        assert_eq!(radio.seeds().tracks, vec!["b", "c", "d"]);
+        assert!(RecommendationRadio::new(RecommendationSeeds::default()).is_err());
+    }
+
+    #[test]
+    fn test_recommendation_seeds_routing() {
+        let seeds = RecommendationSeeds::default()
+            .uri("spotify:artist:4NHQUGzhtTLFvgF5SZesLK")
+            .unwrap()
+            .genre("rock")
+            .uri("https://open.spotify.com/track/0c6xIDDpzE81m2q797ordA?si=abc")
+            .unwrap();
+        assert_eq!(seeds.artists, vec!["4NHQUGzhtTLFvgF5SZesLK"]);
+        assert_eq!(seeds.genres, vec!["rock"]);
+        assert_eq!(seeds.tracks, vec!["0c6xIDDpzE81m2q797ordA"]);
+        assert_eq!(seeds.len(), 3);
+        assert!(seeds.check().is_ok());
+    }
+
+    #[test]
+    fn test_recommendation_seeds_rejected() {
+        let seeds = RecommendationSeeds::default();
+        assert!(seeds.clone().uri("4NHQUGzhtTLFvgF5SZesLK").is_err());
+        assert!(seeds
+            .clone()
+            .uri("spotify:album:0sNOF9WDwhWunNAHPD3Baj")
+            .is_err());
+        let message = |seeds: &RecommendationSeeds| match seeds
+            .check()
+            .unwrap_err()
+            .downcast::<ClientError>()
+        {
+            Ok(ClientError::InvalidParameter(message)) => message,
+            other => panic!("expected InvalidParameter, got {:?}", other),
+        };
+        assert_eq!(message(&seeds), "recommendations need at least one seed");
+        let too_many = ["pop", "rock", "jazz", "soul", "funk", "disco"]
+            .iter()
+            .fold(seeds, |seeds, genre| seeds.genre(genre));
+        assert_eq!(
+            message(&too_many),
+            "recommendations take at most 5 seeds, got 6"
+        );
+    }
+}
@@ -5,6 +5,95 @@ use super::page::Page;
 use super::playlist::SimplifiedPlaylist;
 use super::show::{SimplifiedEpisode, SimplifiedShow};
 use super::track::FullTrack;
+use crate::senum::SearchType;
+use std::fmt;
+/// Builds the `q` parameter of `Spotify::search` from keywords and field
+/// filters, for example:
+/// ```
+/// use rspotify::model::search::SearchQuery;
+/// let query = SearchQuery::new()
+///     .track("Bohemian Rhapsody")
+///     .artist("Queen")
+///     .year_range(1970, 1979);
+/// assert_eq!(
+///     query.to_string(),
+///     r#"track:"Bohemian Rhapsody" artist:Queen year:1970-1979"#
+/// );
+/// ```
+/// Values with spaces are quoted, and double quotes are dropped from them
+/// since Spotify has no way to escape them.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SearchQuery {
+    terms: Vec<String>,
+}
+
+impl SearchQuery {
+    pub fn new() -> SearchQuery {
+        SearchQuery::default()
+    }
+    /// free keywords, matched against any field
+    pub fn keywords(mut self, keywords: &str) -> SearchQuery {
+        self.terms.push(keywords.to_owned());
+        self
+    }
+    pub fn track(self, track: &str) -> SearchQuery {
+        self.filter("track", track)
+    }
+    pub fn artist(self, artist: &str) -> SearchQuery {
+        self.filter("artist", artist)
+    }
+    pub fn album(self, album: &str) -> SearchQuery {
+        self.filter("album", album)
+    }
+    /// only for artist and track searches
+    pub fn genre(self, genre: &str) -> SearchQuery {
+        self.filter("genre", genre)
+    }
+    pub fn isrc(self, isrc: &str) -> SearchQuery {
+        self.filter("isrc", isrc)
+    }
+    pub fn upc(self, upc: &str) -> SearchQuery {
+        self.filter("upc", upc)
+    }
+    pub fn year(self, year: u32) -> SearchQuery {
+        self.filter("year", &year.to_string())
+    }
+    /// released between `from` and `to`, both included
+    pub fn year_range(self, from: u32, to: u32) -> SearchQuery {
+        self.filter("year", &format!("{}-{}", from, to))
+    }
+    /// only albums released in the past two weeks, for album searches
+    pub fn tag_new(self) -> SearchQuery {
+        self.filter("tag", "new")
+    }
+    /// only albums with the lowest 10% popularity, for album searches
+    pub fn tag_hipster(self) -> SearchQuery {
+        self.filter("tag", "hipster")
+    }
+    fn filter(mut self, field: &str, value: &str) -> SearchQuery {
+        let value = value.replace('"', "");
+        let term = if value.contains(char::is_whitespace) {
+            format!("{}:\"{}\"", field, value)
+        } else {
+            format!("{}:{}", field, value)
+        };
+        self.terms.push(term);
+        self
+    }
+}
+
+impl fmt::Display for SearchQuery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.terms.join(" "))
+    }
+}
+
+impl From<SearchQuery> for String {
+    fn from(query: SearchQuery) -> String {
+        query.to_string()
+    }
+}
+
 ///[search item](https://developer.spotify.com/web-api/search-item/);
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SearchPlaylists {
@@ -38,6 +127,24 @@ pub struct SearchEpisodes {
     pub episodes: Page<SimplifiedEpisode>,
 }
 
+/// Evaluate `$body` with `$page` bound to the page of a `SearchResult`,
+/// whatever its type
+macro_rules! on_page {
+    ($result:expr, $page:ident => $body:expr) => {
+        match $result {
+            SearchResult::Playlists($page) => $body,
+            SearchResult::Albums($page) => $body,
+            SearchResult::Artists($page) => $body,
+            SearchResult::Tracks($page) => $body,
+            SearchResult::Shows($page) => $body,
+            SearchResult::Episodes($page) => $body,
+        }
+    };
+}
+
+///[search item](https://developer.spotify.com/web-api/search-item/)
+///Holds the items of the type the search was made for. The accessors fail
+///naming both types when asked for another type, instead of a bare `None`.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum SearchResult {
     #[serde(rename = "playlists")]
@@ -53,3 +160,290 @@ pub enum SearchResult {
     #[serde(rename = "episodes")]
     Episodes(Page<SimplifiedEpisode>),
 }
+
+impl SearchResult {
+    /// The type the search was made for
+    pub fn search_type(&self) -> SearchType {
+        match *self {
+            SearchResult::Playlists(_) => SearchType::Playlist,
+            SearchResult::Albums(_) => SearchType::Album,
+            SearchResult::Artists(_) => SearchType::Artist,
+            SearchResult::Tracks(_) => SearchType::Track,
+            SearchResult::Shows(_) => SearchType::Show,
+            SearchResult::Episodes(_) => SearchType::Episode,
+        }
+    }
+
+    /// The total number of items matching the search, which can be more
+    /// than can be reached by paging, see `Spotify::search_all`
+    pub fn total(&self) -> u32 {
+        on_page!(self, page => page.total)
+    }
+
+    /// The number of items in this result
+    pub fn len(&self) -> usize {
+        on_page!(self, page => page.items.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether Spotify has a next page of results
+    pub fn has_next(&self) -> bool {
+        on_page!(self, page => page.has_next())
+    }
+
+    /// Offset to request the next page of this search with, `None` when
+    /// there's no next page. A search covers a single type, so paging it
+    /// doesn't affect searches made for other types.
+    pub fn next_offset(&self) -> Option<u32> {
+        on_page!(self, page => page.next_offset())
+    }
+
+    /// Append the items of the next page of the same search, see
+    /// `Page::append`
+    pub fn append(&mut self, next: SearchResult) -> Result<(), failure::Error> {
+        match (self, next) {
+            (SearchResult::Playlists(page), SearchResult::Playlists(next)) => page.append(next),
+            (SearchResult::Albums(page), SearchResult::Albums(next)) => page.append(next),
+            (SearchResult::Artists(page), SearchResult::Artists(next)) => page.append(next),
+            (SearchResult::Tracks(page), SearchResult::Tracks(next)) => page.append(next),
+            (SearchResult::Shows(page), SearchResult::Shows(next)) => page.append(next),
+            (SearchResult::Episodes(page), SearchResult::Episodes(next)) => page.append(next),
+            (this, next) => return Err(next.mismatch(this.search_type())),
+        }
+        Ok(())
+    }
+
+    fn mismatch(&self, requested: SearchType) -> failure::Error {
+        format_err!(
+            "the search was made for {}s, not {}s",
+            self.search_type().as_str(),
+            requested.as_str()
+        )
+    }
+
+    pub fn playlists(&self) -> Result<&Page<SimplifiedPlaylist>, failure::Error> {
+        match *self {
+            SearchResult::Playlists(ref page) => Ok(page),
+            _ => Err(self.mismatch(SearchType::Playlist)),
+        }
+    }
+
+    pub fn albums(&self) -> Result<&Page<SimplifiedAlbum>, failure::Error> {
+        match *self {
+            SearchResult::Albums(ref page) => Ok(page),
+            _ => Err(self.mismatch(SearchType::Album)),
+        }
+    }
+
+    pub fn artists(&self) -> Result<&Page<FullArtist>, failure::Error> {
+        match *self {
+            SearchResult::Artists(ref page) => Ok(page),
+            _ => Err(self.mismatch(SearchType::Artist)),
+        }
+    }
+
+    pub fn tracks(&self) -> Result<&Page<FullTrack>, failure::Error> {
+        match *self {
+            SearchResult::Tracks(ref page) => Ok(page),
+            _ => Err(self.mismatch(SearchType::Track)),
+        }
+    }
+
+    pub fn shows(&self) -> Result<&Page<SimplifiedShow>, failure::Error> {
+        match *self {
+            SearchResult::Shows(ref page) => Ok(page),
+            _ => Err(self.mismatch(SearchType::Show)),
+        }
+    }
+
+    pub fn episodes(&self) -> Result<&Page<SimplifiedEpisode>, failure::Error> {
+        match *self {
+            SearchResult::Episodes(ref page) => Ok(page),
+            _ => Err(self.mismatch(SearchType::Episode)),
+        }
+    }
+
+    /// Take the playlists out of the result, see `playlists`
+    pub fn into_playlists(self) -> Result<Page<SimplifiedPlaylist>, failure::Error> {
+        match self {
+            SearchResult::Playlists(page) => Ok(page),
+            _ => Err(self.mismatch(SearchType::Playlist)),
+        }
+    }
+
+    /// Take the albums out of the result, see `albums`
+    pub fn into_albums(self) -> Result<Page<SimplifiedAlbum>, failure::Error> {
+        match self {
+            SearchResult::Albums(page) => Ok(page),
+            _ => Err(self.mismatch(SearchType::Album)),
+        }
+    }
+
+    /// Take the artists out of the result, see `artists`
+    pub fn into_artists(self) -> Result<Page<FullArtist>, failure::Error> {
+        match self {
+            SearchResult::Artists(page) => Ok(page),
+            _ => Err(self.mismatch(SearchType::Artist)),
+        }
+    }
+
+    /// Take the tracks out of the result, see `tracks`
+    pub fn into_tracks(self) -> Result<Page<FullTrack>, failure::Error> {
+        match self {
+            SearchResult::Tracks(page) => Ok(page),
+            _ => Err(self.mismatch(SearchType::Track)),
+        }
+    }
+
+    /// Take the shows out of the result, see `shows`
+    pub fn into_shows(self) -> Result<Page<SimplifiedShow>, failure::Error> {
+        match self {
+            SearchResult::Shows(page) => Ok(page),
+            _ => Err(self.mismatch(SearchType::Show)),
+        }
+    }
+
+    /// Take the episodes out of the result, see `episodes`
+    pub fn into_episodes(self) -> Result<Page<SimplifiedEpisode>, failure::Error> {
+        match self {
+            SearchResult::Episodes(page) => Ok(page),
+            _ => Err(self.mismatch(SearchType::Episode)),
+        }
+    }
+}
+
+/// Every result of a search that `Spotify::search_all` could reach
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SearchAll {
+    /// The items of every page, appended to the first one
+    pub result: SearchResult,
+    /// Whether more items match than could be fetched: Spotify stops paging
+    /// search results at an offset of 1000, whatever the `total`
+    pub truncated: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_result_accessors() {
+        let result: SearchResult = serde_json::from_str(
+            r#"{
+                "artists": {
+                    "href": "https://api.spotify.com/v1/search?query=tania+bowra&type=artist&offset=0&limit=20",
+                    "items": [],
+                    "limit": 20,
+                    "next": null,
+                    "offset": 0,
+                    "previous": null,
+                    "total": 0
+                }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(result.search_type(), SearchType::Artist);
+        assert!(result.artists().is_ok());
+        let err = result.tracks().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the search was made for artists, not tracks"
+        );
+    }
+
+    #[test]
+    fn test_search_result_into_page() {
+        let page = artists_page(0, &["a", "b"], 2).into_artists().unwrap();
+        assert_eq!(page.items.len(), 2);
+        let err = artists_page(0, &["a"], 1).into_albums().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the search was made for artists, not albums"
+        );
+    }
+
+    fn artists_page(offset: u32, ids: &[&str], total: u32) -> SearchResult {
+        let items: Vec<_> = ids
+            .iter()
+            .map(|id| {
+                serde_json::json!({
+                    "external_urls": {},
+                    "followers": {"href": null, "total": 0},
+                    "genres": [],
+                    "href": "",
+                    "id": id,
+                    "images": [],
+                    "name": id,
+                    "popularity": 0,
+                    "type": "artist",
+                    "uri": ""
+                })
+            })
+            .collect();
+        let next = offset + (ids.len() as u32) < total;
+        serde_json::from_value(serde_json::json!({
+            "artists": {
+                "href": "",
+                "items": items,
+                "limit": 2,
+                "next": if next { Some("https://api.spotify.com/v1/search") } else { None },
+                "offset": offset,
+                "previous": null,
+                "total": total
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_search_result_append() {
+        let mut result = artists_page(0, &["a", "b"], 3);
+        assert_eq!(result.len(), 2);
+        assert_eq!(result.total(), 3);
+        assert!(result.has_next());
+        assert_eq!(result.next_offset(), Some(2));
+        result.append(artists_page(2, &["c"], 3)).unwrap();
+        assert_eq!(result.len(), 3);
+        assert!(!result.has_next());
+        assert_eq!(result.next_offset(), None);
+        let ids: Vec<_> = result
+            .artists()
+            .unwrap()
+            .items
+            .iter()
+            .map(|artist| artist.id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["a", "b", "c"]);
+
+        let tracks: SearchResult = serde_json::from_value(serde_json::json!({
+            "tracks": {
+                "href": "", "items": [], "limit": 2, "next": null,
+                "offset": 0, "previous": null, "total": 0
+            }
+        }))
+        .unwrap();
+        let err = result.append(tracks).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the search was made for tracks, not artists"
+        );
+    }
+
+    #[test]
+    fn test_search_query() {
+        let query = SearchQuery::new()
+            .keywords("live")
+            .album("A Night at the \"Opera\"")
+            .genre("rock")
+            .tag_hipster();
+        assert_eq!(
+            String::from(query),
+            r#"live album:"A Night at the Opera" genre:rock tag:hipster"#
+        );
+        assert_eq!(SearchQuery::new().year(1999).to_string(), "year:1999");
+        assert_eq!(SearchQuery::new().to_string(), "");
+    }
+}
@@ -35,6 +35,13 @@ pub struct Show {
     pub show: SimplifiedShow,
 }
 
+/// [Saved episode object](https://developer.spotify.com/documentation/web-api/reference/#endpoint-get-users-saved-episodes)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SavedEpisode {
+    pub added_at: String,
+    pub episode: FullEpisode,
+}
+
 /// [Show object(full)](https://developer.spotify.com/documentation/web-api/reference/object-model/#show-object-full)
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct FullShow {
@@ -76,6 +83,8 @@ pub struct SimplifiedEpisode {
     pub name: String,
     pub release_date: String,
     pub release_date_precision: String,
+    /// Only present for a user token with the `user-read-playback-position`
+    /// scope
     pub resume_point: Option<ResumePoint>,
     #[serde(rename = "type")]
     pub _type: String,
@@ -101,6 +110,8 @@ pub struct FullEpisode {
     pub name: String,
     pub release_date: String,
     pub release_date_precision: String,
+    /// Only present for a user token with the `user-read-playback-position`
+    /// scope
     pub resume_point: Option<ResumePoint>,
     pub show: SimplifiedShow,
     #[serde(rename = "type")]
@@ -67,6 +67,12 @@ pub struct SimplifiedTrack {
     pub href: Option<String>,
     pub id: Option<String>,
     pub is_local: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_playable: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub linked_from: Option<TrackLink>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub restrictions: Option<Restrictions>,
     pub name: String,
     pub preview_url: Option<String>,
     pub track_number: u32,
@@ -81,3 +87,50 @@ pub struct SavedTrack {
     pub added_at: DateTime<Utc>,
     pub track: FullTrack,
 }
+
+/// Outcome of `Spotify::current_user_saved_tracks_add_reporting`: which of
+/// the tracks were saved by the call, and which were already saved before it
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AddReport {
+    /// IDs of the tracks that weren't saved yet
+    pub newly_added: Vec<String>,
+    /// IDs of the tracks that were already saved
+    pub already_present: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simplified_track_relinking() {
+        // Recorded from `albums/{id}/tracks?market=ES`, trimmed down
+        let json = r#"{
+            "artists": [],
+            "disc_number": 1,
+            "duration_ms": 207959,
+            "explicit": false,
+            "external_urls": {"spotify": "https://open.spotify.com/track/6rqhFgbbKwnb9MLmUQDhG6"},
+            "href": "https://api.spotify.com/v1/tracks/6rqhFgbbKwnb9MLmUQDhG6",
+            "id": "6rqhFgbbKwnb9MLmUQDhG6",
+            "is_local": false,
+            "is_playable": true,
+            "linked_from": {
+                "external_urls": {"spotify": "https://open.spotify.com/track/2jpDioAB9tlYXMdXDK3BGl"},
+                "href": "https://api.spotify.com/v1/tracks/2jpDioAB9tlYXMdXDK3BGl",
+                "id": "2jpDioAB9tlYXMdXDK3BGl",
+                "type": "track",
+                "uri": "spotify:track:2jpDioAB9tlYXMdXDK3BGl"
+            },
+            "name": "Speak Now",
+            "preview_url": null,
+            "track_number": 1,
+            "type": "track",
+            "uri": "spotify:track:6rqhFgbbKwnb9MLmUQDhG6"
+        }"#;
+        let track: SimplifiedTrack = serde_json::from_str(json).unwrap();
+        assert_eq!(track.is_playable, Some(true));
+        assert_eq!(track.linked_from.unwrap().id, "2jpDioAB9tlYXMdXDK3BGl");
+        assert!(track.available_markets.is_none());
+    }
+}
@@ -3,36 +3,254 @@ use chrono::NaiveDate;
 use serde_json::Value;
 
 use std::collections::HashMap;
+use std::str::FromStr;
 
 use super::image::Image;
-use crate::senum::Type;
+use crate::senum::{Country, SubscriptionLevel, Type};
 ///[public user object](https://developer.spotify.com/web-api/object-model/#user-object-public)
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PublicUser {
     pub display_name: Option<String>,
     pub external_urls: HashMap<String, String>,
     pub followers: Option<HashMap<String, Option<Value>>>,
+    /// Web API endpoint of the user's profile
     pub href: String,
     pub id: String,
     pub images: Option<Vec<Image>>,
     #[serde(rename = "type")]
     pub _type: Type,
+    /// Spotify URI of the user, `spotify:user:{id}`, for deep links
     pub uri: String,
 }
 
+impl PublicUser {
+    /// The name to show for this user, `fallback` when they have no display
+    /// name
+    pub fn display_name_or<'a>(&'a self, fallback: &'a str) -> &'a str {
+        self.display_name.as_deref().unwrap_or(fallback)
+    }
+}
+
 ///[private user object](https://developer.spotify.com/web-api/object-model/#user-object-private)
+///
+///`id`, `href`, `uri` and `external_urls` are always present; the fields
+///tied to a scope are missing when the token wasn't granted it.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PrivateUser {
+    /// Only present with the `user-read-birthdate` scope, which Spotify
+    /// no longer grants
     pub birthdate: Option<NaiveDate>,
+    /// ISO 3166-1 alpha-2 code, only present with the `user-read-private`
+    /// scope. See `market` for the parsed country.
     pub country: Option<String>,
     pub display_name: Option<String>,
+    /// Only present with the `user-read-email` scope
     pub email: Option<String>,
+    /// Only present with the `user-read-private` scope
+    pub explicit_content: Option<ExplicitContent>,
     pub external_urls: HashMap<String, String>,
     pub followers: Option<HashMap<String, Option<Value>>>,
+    /// Web API endpoint of the user's profile
     pub href: String,
     pub id: String,
     pub images: Option<Vec<Image>>,
+    /// Only present with the `user-read-private` scope
+    pub product: Option<SubscriptionLevel>,
     #[serde(rename = "type")]
     pub _type: Type,
+    /// Spotify URI of the user, `spotify:user:{id}`, for deep links
     pub uri: String,
 }
+
+impl PrivateUser {
+    /// The name to show for this user, `fallback` when they have no display
+    /// name
+    pub fn display_name_or<'a>(&'a self, fallback: &'a str) -> &'a str {
+        self.display_name.as_deref().unwrap_or(fallback)
+    }
+
+    /// Whether explicit content may be played or suggested to this user. It's
+    /// only disallowed when the user (or their parent account) enabled the
+    /// explicit content filter; without the `user-read-private` scope
+    /// `explicit_content` is missing and explicit content is allowed.
+    pub fn explicit_allowed(&self) -> bool {
+        match self.explicit_content {
+            Some(ref explicit_content) => !explicit_content.filter_enabled,
+            None => true,
+        }
+    }
+
+    /// Whether the user can be controlled through the player endpoints, which
+    /// require Spotify Premium. Without the `user-read-private` scope the
+    /// subscription is unknown and this returns false.
+    pub fn can_control_playback(&self) -> bool {
+        self.product == Some(SubscriptionLevel::Premium)
+    }
+
+    /// The country of the user's account, which is the market their content
+    /// availability is based on. `None` without the `user-read-private`
+    /// scope, or if Spotify sends a code `Country` doesn't know.
+    pub fn market(&self) -> Option<Country> {
+        self.country
+            .as_ref()
+            .and_then(|country| Country::from_str(country).ok())
+    }
+}
+
+///[explicit content settings object](https://developer.spotify.com/documentation/web-api/reference/object-model/#user-object-private)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ExplicitContent {
+    /// When true, the user has chosen not to play explicit content
+    pub filter_enabled: bool,
+    /// When true, the setting is locked and can't be changed by the user
+    pub filter_locked: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn private_user(explicit_content: &str) -> PrivateUser {
+        let json = format!(
+            r#"{{
+                "country": "SE",
+                "display_name": "JM Wizzler",
+                "email": "email@example.com",
+                {}
+                "external_urls": {{"spotify": "https://open.spotify.com/user/wizzler"}},
+                "followers": {{"href": null, "total": 3829}},
+                "href": "https://api.spotify.com/v1/users/wizzler",
+                "id": "wizzler",
+                "images": [],
+                "product": "premium",
+                "type": "user",
+                "uri": "spotify:user:wizzler"
+            }}"#,
+            explicit_content
+        );
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn test_explicit_allowed() {
+        let user = private_user(
+            r#""explicit_content": {"filter_enabled": true, "filter_locked": false},"#,
+        );
+        assert!(!user.explicit_allowed());
+        let user = private_user(
+            r#""explicit_content": {"filter_enabled": false, "filter_locked": false},"#,
+        );
+        assert!(user.explicit_allowed());
+        assert!(private_user("").explicit_allowed());
+    }
+
+    #[test]
+    fn test_can_control_playback() {
+        let user = private_user("");
+        assert_eq!(user.product, Some(SubscriptionLevel::Premium));
+        assert!(user.can_control_playback());
+        let user: PrivateUser = serde_json::from_str(
+            r#"{
+                "external_urls": {},
+                "href": "https://api.spotify.com/v1/users/wizzler",
+                "id": "wizzler",
+                "product": "open",
+                "type": "user",
+                "uri": "spotify:user:wizzler"
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(user.product, Some(SubscriptionLevel::Open));
+        assert!(!user.can_control_playback());
+        // a level this crate doesn't know doesn't fail the profile
+        let user: PrivateUser = serde_json::from_str(
+            r#"{
+                "external_urls": {},
+                "href": "https://api.spotify.com/v1/users/wizzler",
+                "id": "wizzler",
+                "product": "daypass",
+                "type": "user",
+                "uri": "spotify:user:wizzler"
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(user.product, Some(SubscriptionLevel::Unknown));
+        assert!(!user.can_control_playback());
+    }
+
+    #[test]
+    fn test_display_name_or() {
+        let user = private_user("");
+        assert_eq!(user.display_name_or("Dear"), "JM Wizzler");
+        let user: PublicUser = serde_json::from_str(
+            r#"{
+                "display_name": null,
+                "external_urls": {},
+                "href": "https://api.spotify.com/v1/users/wizzler",
+                "id": "wizzler",
+                "type": "user",
+                "uri": "spotify:user:wizzler"
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(user.display_name_or("Dear"), "Dear");
+    }
+
+    #[test]
+    fn test_private_user_scopes() {
+        let user = private_user("");
+        assert_eq!(user.country.as_deref(), Some("SE"));
+        assert_eq!(user.market(), Some(Country::Sweden));
+        assert_eq!(user.email.as_deref(), Some("email@example.com"));
+        assert_eq!(user.followers.unwrap()["total"], Some(Value::from(3829)));
+        assert_eq!(user.images.unwrap().len(), 0);
+        assert_eq!(user.href, "https://api.spotify.com/v1/users/wizzler");
+        assert_eq!(user.uri, "spotify:user:wizzler");
+
+        // What `me` answers with a token granted no scope at all
+        let user: PrivateUser = serde_json::from_str(
+            r#"{
+                "display_name": "JM Wizzler",
+                "external_urls": {"spotify": "https://open.spotify.com/user/wizzler"},
+                "followers": {"href": null, "total": 3829},
+                "href": "https://api.spotify.com/v1/users/wizzler",
+                "id": "wizzler",
+                "images": [],
+                "type": "user",
+                "uri": "spotify:user:wizzler"
+            }"#,
+        )
+        .unwrap();
+        assert!(user.birthdate.is_none());
+        assert!(user.country.is_none());
+        assert!(user.market().is_none());
+        assert!(user.email.is_none());
+        assert!(user.explicit_content.is_none());
+        assert!(user.product.is_none());
+        assert_eq!(
+            user.external_urls["spotify"],
+            "https://open.spotify.com/user/wizzler"
+        );
+    }
+
+    #[test]
+    fn test_user_uri_round_trip() {
+        let user = private_user("");
+        assert_eq!(user._type, Type::User);
+        assert_eq!(
+            user.uri,
+            format!("spotify:{}:{}", Type::User.as_str(), user.id)
+        );
+        assert_eq!(
+            user.href,
+            format!("https://api.spotify.com/v1/users/{}", user.id)
+        );
+        let value = serde_json::to_value(&user).unwrap();
+        assert_eq!(value["uri"], "spotify:user:wizzler");
+        assert_eq!(value["href"], "https://api.spotify.com/v1/users/wizzler");
+        assert_eq!(value["type"], "user");
+        let public: PublicUser = serde_json::from_value(value).unwrap();
+        assert_eq!(public.uri, user.uri);
+        assert_eq!(public.href, user.href);
+    }
+}
@@ -3,19 +3,23 @@
 use chrono::prelude::*;
 use dotenv::dotenv;
 use percent_encoding::{utf8_percent_encode, PATH_SEGMENT_ENCODE_SET};
-use reqwest::Client;
+use reqwest::{Client, RequestBuilder};
 
 // use built-in library
 use std::collections::{HashMap, HashSet};
 use std::env;
+use std::fmt;
 use std::fs::File;
 use std::fs::OpenOptions;
 use std::io::prelude::*;
 use std::iter::FromIterator;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 
 // use customized library
-use super::util::{convert_map_to_string, datetime_to_timestamp, generate_random_string};
+use super::util::{
+    convert_map_to_string, datetime_to_timestamp, generate_random_string, read_env_vars, read_vars,
+};
 
 /// Client credentials object for spotify
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -23,6 +27,16 @@ pub struct SpotifyClientCredentials {
     pub client_id: String,
     pub client_secret: String,
     pub token_info: Option<TokenInfo>,
+    /// App token obtained through the client-credentials flow. It's shared
+    /// between clones, so every `Spotify` built from the same credentials
+    /// reuses it until it expires.
+    #[serde(skip)]
+    app_token: Arc<Mutex<Option<TokenInfo>>>,
+    #[serde(skip)]
+    on_token_refresh: Option<TokenCallback>,
+    /// Where tokens are requested, `TOKEN_URL` but in tests
+    #[serde(skip, default = "default_token_url")]
+    token_url: String,
 }
 /// Authorization for spotify
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -34,6 +48,29 @@ pub struct SpotifyOAuth {
     pub cache_path: PathBuf,
     pub scope: String,
     pub proxies: Option<String>,
+    #[serde(skip)]
+    on_token_refresh: Option<TokenCallback>,
+    /// Where tokens are requested, `TOKEN_URL` but in tests
+    #[serde(skip, default = "default_token_url")]
+    token_url: String,
+}
+
+/// Endpoint of the token requests of every flow
+const TOKEN_URL: &str = "https://accounts.spotify.com/api/token";
+fn default_token_url() -> String {
+    TOKEN_URL.to_owned()
+}
+
+/// Callback run with every token that is obtained or refreshed, see
+/// `SpotifyOAuth::on_token_refresh`. Its error is returned instead of the
+/// token.
+#[derive(Clone)]
+pub struct TokenCallback(Arc<TokenRefreshFn>);
+type TokenRefreshFn = dyn Fn(&TokenInfo) -> Result<(), failure::Error> + Send + Sync;
+impl fmt::Debug for TokenCallback {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("TokenCallback")
+    }
 }
 
 /// Spotify token-info
@@ -88,6 +125,11 @@ impl TokenInfo {
     pub fn set_refresh_token(&mut self, refresh_token: &str) {
         self.refresh_token = Some(refresh_token.to_owned());
     }
+    /// `expires_at` as a date, `None` if it isn't known
+    pub fn expires_at_datetime(&self) -> Option<DateTime<Utc>> {
+        self.expires_at
+            .and_then(|expires_at| Utc.timestamp_opt(expires_at, 0).single())
+    }
 }
 
 impl SpotifyClientCredentials {
@@ -105,8 +147,20 @@ impl SpotifyClientCredentials {
             client_id,
             client_secret,
             token_info: None,
+            app_token: Arc::new(Mutex::new(None)),
+            on_token_refresh: None,
+            token_url: default_token_url(),
         }
     }
+    /// build SpotifyClientCredentials from the `RSPOTIFY_CLIENT_ID` and
+    /// `RSPOTIFY_CLIENT_SECRET` environment variables (or `.env` file). Fails
+    /// naming the variables that are missing.
+    pub fn from_env() -> Result<SpotifyClientCredentials, failure::Error> {
+        let vars = read_env_vars(&["RSPOTIFY_CLIENT_ID", "RSPOTIFY_CLIENT_SECRET"])?;
+        Ok(SpotifyClientCredentials::default()
+            .client_id(&vars[0])
+            .client_secret(&vars[1]))
+    }
     pub fn client_id(mut self, client_id: &str) -> SpotifyClientCredentials {
         self.client_id = client_id.to_owned();
         self
@@ -115,6 +169,19 @@ impl SpotifyClientCredentials {
         self.client_secret = client_secret.to_owned();
         self
     }
+    /// run `callback` with every app token requested through the
+    /// client-credentials flow, before it's used. When it fails, e.g. to
+    /// persist the token, the request that needed the token fails with its
+    /// error.
+    pub fn on_token_refresh<
+        F: Fn(&TokenInfo) -> Result<(), failure::Error> + Send + Sync + 'static,
+    >(
+        mut self,
+        callback: F,
+    ) -> SpotifyClientCredentials {
+        self.on_token_refresh = Some(TokenCallback(Arc::new(callback)));
+        self
+    }
     pub fn token_info(mut self, token_info: TokenInfo) -> SpotifyClientCredentials {
         self.token_info = Some(token_info);
         self
@@ -141,34 +208,56 @@ impl SpotifyClientCredentials {
         self
     }
     /// get access token from self.token_info, if self.token_info is none or is
-    /// expired. fetch token info by HTTP request
-    pub async fn get_access_token(&self) -> String {
-        let access_token = match self.token_info {
-            Some(ref token_info) => {
-                if !self.is_token_expired(token_info) {
-                    debug!("token info: {:?}", &token_info);
-                    Some(&token_info.access_token)
-                } else {
-                    None
-                }
+    /// expired, fall back to the app token of the client-credentials flow.
+    /// The app token is cached and, since this flow has no refresh token,
+    /// requested again once it expires.
+    pub async fn get_access_token(&self) -> Result<String, failure::Error> {
+        if let Some(ref token_info) = self.token_info {
+            if !self.is_token_expired(token_info) {
+                debug!("token info: {:?}", &token_info);
+                return Ok(token_info.access_token.to_owned());
             }
-            None => None,
-        };
-        match access_token {
-            Some(access_token) => access_token.to_owned(),
-            None => match self.request_access_token().await {
-                Some(new_token_info) => {
-                    debug!("token info: {:?}", &new_token_info);
-                    new_token_info.access_token
-                }
-                None => String::new(),
-            },
+        }
+        if let Some(token_info) = self.cached_app_token() {
+            debug!("cached app token info: {:?}", &token_info);
+            return Ok(token_info.access_token);
+        }
+        match self.request_access_token().await? {
+            Some(new_token_info) => {
+                debug!("token info: {:?}", &new_token_info);
+                let access_token = new_token_info.access_token.to_owned();
+                *self.app_token.lock().unwrap() = Some(new_token_info);
+                Ok(access_token)
+            }
+            None => Ok(String::new()),
+        }
+    }
+    /// the cached app token, if it hasn't expired yet
+    fn cached_app_token(&self) -> Option<TokenInfo> {
+        let app_token = self.app_token.lock().unwrap();
+        match *app_token {
+            Some(ref token_info) if !self.is_token_expired(token_info) => Some(token_info.clone()),
+            _ => None,
+        }
+    }
+    /// When the token in use expires: the expiry of `token_info` when it's
+    /// set, otherwise the one of the app token obtained through the
+    /// client-credentials flow, if one was obtained yet.
+    pub fn token_expires_at(&self) -> Option<DateTime<Utc>> {
+        match self.token_info {
+            Some(ref token_info) => token_info.expires_at_datetime(),
+            None => self
+                .app_token
+                .lock()
+                .unwrap()
+                .as_ref()
+                .and_then(TokenInfo::expires_at_datetime),
         }
     }
     fn is_token_expired(&self, token_info: &TokenInfo) -> bool {
         is_token_expired(token_info)
     }
-    async fn request_access_token(&self) -> Option<TokenInfo> {
+    async fn request_access_token(&self) -> Result<Option<TokenInfo>, failure::Error> {
         let mut payload = HashMap::new();
         payload.insert("grant_type", "client_credentials");
         if let Some(mut token_info) = self
@@ -177,9 +266,12 @@ impl SpotifyClientCredentials {
         {
             let expires_in = token_info.expires_in;
             token_info.set_expires_at(datetime_to_timestamp(expires_in));
-            Some(token_info)
+            if let Some(ref callback) = self.on_token_refresh {
+                (callback.0)(&token_info)?;
+            }
+            Ok(Some(token_info))
         } else {
-            None
+            Ok(None)
         }
     }
     async fn fetch_access_token(
@@ -188,7 +280,7 @@ impl SpotifyClientCredentials {
         client_secret: &str,
         payload: &HashMap<&str, &str>,
     ) -> Option<TokenInfo> {
-        fetch_access_token(client_id, client_secret, payload).await
+        fetch_access_token(&self.token_url, client_id, client_secret, payload).await
     }
 }
 
@@ -215,8 +307,38 @@ impl SpotifyOAuth {
             scope: String::new(),
             cache_path: PathBuf::from(".spotify_token_cache.json"),
             proxies: None,
+            on_token_refresh: None,
+            token_url: default_token_url(),
         }
     }
+    /// build SpotifyOAuth from the `RSPOTIFY_CLIENT_ID`,
+    /// `RSPOTIFY_CLIENT_SECRET` and `RSPOTIFY_REDIRECT_URI` environment
+    /// variables (or `.env` file), plus the optional space-delimited
+    /// `RSPOTIFY_SCOPES`. Fails naming the required variables that are
+    /// missing.
+    pub fn from_env() -> Result<SpotifyOAuth, failure::Error> {
+        dotenv().ok();
+        SpotifyOAuth::from_lookup(|name| env::var(name).ok())
+    }
+    /// `from_env` with the variables read through `lookup`
+    fn from_lookup(
+        lookup: impl Fn(&str) -> Option<String>,
+    ) -> Result<SpotifyOAuth, failure::Error> {
+        let vars = read_vars(
+            &[
+                "RSPOTIFY_CLIENT_ID",
+                "RSPOTIFY_CLIENT_SECRET",
+                "RSPOTIFY_REDIRECT_URI",
+            ],
+            &lookup,
+        )?;
+        let scope = lookup("RSPOTIFY_SCOPES").unwrap_or_default();
+        Ok(SpotifyOAuth::default()
+            .client_id(&vars[0])
+            .client_secret(&vars[1])
+            .redirect_uri(&vars[2])
+            .scope(&scope))
+    }
     pub fn client_id(mut self, client_id: &str) -> SpotifyOAuth {
         self.client_id = client_id.to_owned();
         self
@@ -241,6 +363,19 @@ impl SpotifyOAuth {
         self.cache_path = cache_path;
         self
     }
+    /// run `callback` with every token obtained from a code or refreshed,
+    /// before it's cached and returned, e.g. to persist it somewhere else
+    /// than `cache_path`. When it fails, its error is returned instead of the
+    /// token, which isn't cached.
+    pub fn on_token_refresh<
+        F: Fn(&TokenInfo) -> Result<(), failure::Error> + Send + Sync + 'static,
+    >(
+        mut self,
+        callback: F,
+    ) -> SpotifyOAuth {
+        self.on_token_refresh = Some(TokenCallback(Arc::new(callback)));
+        self
+    }
     pub fn proxies(mut self, proxies: &str) -> SpotifyOAuth {
         self.proxies = Some(proxies.to_owned());
         self
@@ -269,42 +404,48 @@ impl SpotifyOAuth {
         }
         self
     }
-    pub async fn get_cached_token(&mut self) -> Option<TokenInfo> {
+    pub async fn get_cached_token(&mut self) -> Result<Option<TokenInfo>, failure::Error> {
         let display = self.cache_path.display();
         let mut file = match File::open(&self.cache_path) {
             Ok(file) => file,
             Err(why) => {
                 error!("couldn't open {}: {:?}", display, why.to_string());
-                return None;
+                return Ok(None);
             }
         };
         let mut token_info_string = String::new();
         match file.read_to_string(&mut token_info_string) {
             Err(why) => {
                 error!("couldn't read {}: {}", display, why.to_string());
-                None
+                Ok(None)
             }
             Ok(_) => {
                 let mut token_info: TokenInfo = serde_json::from_str(&token_info_string)
                     .unwrap_or_else(|_| {
                         panic!("convert [{:?}] to json failed", self.cache_path.display())
                     });
+                if token_info.access_token.trim().is_empty() {
+                    warn!("the token cached in {} has no access token", display);
+                }
                 if !SpotifyOAuth::is_scope_subset(&mut self.scope, &mut token_info.scope) {
-                    None
+                    Ok(None)
                 } else if self.is_token_expired(&token_info) {
                     if let Some(refresh_token) = token_info.refresh_token {
                         self.refresh_access_token(&refresh_token).await
                     } else {
-                        None
+                        Ok(None)
                     }
                 } else {
-                    Some(token_info)
+                    Ok(Some(token_info))
                 }
             }
         }
     }
     /// gets the access_token for the app with the given code without caching(without saving token to `cache_path`)
-    pub async fn get_access_token_without_cache(&self, code: &str) -> Option<TokenInfo> {
+    pub async fn get_access_token_without_cache(
+        &self,
+        code: &str,
+    ) -> Result<Option<TokenInfo>, failure::Error> {
         let mut payload: HashMap<&str, &str> = HashMap::new();
         payload.insert("redirect_uri", &self.redirect_uri);
         payload.insert("code", code);
@@ -316,13 +457,13 @@ impl SpotifyOAuth {
     }
 
     /// gets the access_token for the app with given the code
-    pub async fn get_access_token(&self, code: &str) -> Option<TokenInfo> {
-        if let Some(token_info) = self.get_access_token_without_cache(code).await {
+    pub async fn get_access_token(&self, code: &str) -> Result<Option<TokenInfo>, failure::Error> {
+        if let Some(token_info) = self.get_access_token_without_cache(code).await? {
             match serde_json::to_string(&token_info) {
                 Ok(token_info_string) => {
                     trace!("get_access_token->token_info[{:?}]", &token_info_string);
                     self.save_token_info(&token_info_string);
-                    Some(token_info)
+                    Ok(Some(token_info))
                 }
                 Err(why) => {
                     panic!(
@@ -332,7 +473,7 @@ impl SpotifyOAuth {
                 }
             }
         } else {
-            None
+            Ok(None)
         }
     }
     /// fetch access_token
@@ -341,9 +482,14 @@ impl SpotifyOAuth {
         client_id: &str,
         client_secret: &str,
         payload: &HashMap<&str, &str>,
-    ) -> Option<TokenInfo> {
+    ) -> Result<Option<TokenInfo>, failure::Error> {
         trace!("fetch_access_token->payload {:?}", &payload);
-        fetch_access_token(client_id, client_secret, payload).await
+        let token_info =
+            fetch_access_token(&self.token_url, client_id, client_secret, payload).await;
+        if let (Some(token_info), Some(callback)) = (&token_info, &self.on_token_refresh) {
+            (callback.0)(token_info)?;
+        }
+        Ok(token_info)
     }
 
     /// Parse the response code in the given response url
@@ -383,7 +529,7 @@ impl SpotifyOAuth {
     pub async fn refresh_access_token_without_cache(
         &self,
         refresh_token: &str,
-    ) -> Option<TokenInfo> {
+    ) -> Result<Option<TokenInfo>, failure::Error> {
         let mut payload = HashMap::new();
         payload.insert("refresh_token", refresh_token);
         payload.insert("grant_type", "refresh_token");
@@ -393,12 +539,18 @@ impl SpotifyOAuth {
 
     /// after refresh access_token, the response may be empty
     /// when refresh_token again
-    pub async fn refresh_access_token(&self, refresh_token: &str) -> Option<TokenInfo> {
-        if let Some(token_info) = self.refresh_access_token_without_cache(refresh_token).await {
+    pub async fn refresh_access_token(
+        &self,
+        refresh_token: &str,
+    ) -> Result<Option<TokenInfo>, failure::Error> {
+        if let Some(token_info) = self
+            .refresh_access_token_without_cache(refresh_token)
+            .await?
+        {
             match serde_json::to_string(&token_info) {
                 Ok(token_info_string) => {
                     self.save_token_info(&token_info_string);
-                    Some(token_info)
+                    Ok(Some(token_info))
                 }
                 Err(why) => {
                     panic!(
@@ -408,7 +560,7 @@ impl SpotifyOAuth {
                 }
             }
         } else {
-            None
+            Ok(None)
         }
     }
     fn save_token_info(&self, token_info: &str) {
@@ -427,7 +579,13 @@ impl SpotifyOAuth {
     }
 }
 
+/// Whether a token can't be used anymore. A blank access token, e.g. from a
+/// malformed cache file, counts as expired, so that it's refreshed or
+/// obtained again instead of failing later with a 401 response.
 fn is_token_expired(token_info: &TokenInfo) -> bool {
+    if token_info.access_token.trim().is_empty() {
+        return true;
+    }
     let now: DateTime<Utc> = Utc::now();
     // 10s as buffer time
     match token_info.expires_at {
@@ -451,19 +609,37 @@ fn save_token_info(token_info: &str, path: &Path) {
         .expect("error when write file");
 }
 
+/// Request for a token: the client credentials as basic auth, the rest of
+/// the grant form-encoded, which sets its `Content-Type`. Without a client
+/// secret, as in the PKCE flow, basic auth fails, so the client ID is sent
+/// in the form instead.
+fn token_request(
+    client: &Client,
+    token_url: &str,
+    client_id: &str,
+    client_secret: &str,
+    payload: &HashMap<&str, &str>,
+) -> RequestBuilder {
+    let request = client.post(token_url);
+    if client_secret.is_empty() {
+        let mut payload = payload.clone();
+        payload.insert("client_id", client_id);
+        request.form(&payload)
+    } else {
+        request
+            .basic_auth(client_id, Some(client_secret))
+            .form(payload)
+    }
+}
+
 async fn fetch_access_token(
+    token_url: &str,
     _client_id: &str,
     _client_secret: &str,
     payload: &HashMap<&str, &str>,
 ) -> Option<TokenInfo> {
     let client = Client::new();
-    let client_id = _client_id.to_owned();
-    let client_secret = _client_secret.to_owned();
-    let url = "https://accounts.spotify.com/api/token";
-    let response = client
-        .post(url)
-        .basic_auth(client_id, Some(client_secret))
-        .form(&payload)
+    let response = token_request(&client, token_url, _client_id, _client_secret, payload)
         .send()
         .await
         .expect("send request failed");
@@ -498,9 +674,130 @@ async fn fetch_access_token(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
     use serde_json;
     use std::path::PathBuf;
     #[test]
+    fn test_oauth_from_lookup() {
+        let mut vars = HashMap::new();
+        vars.insert("RSPOTIFY_CLIENT_SECRET", "secret");
+        let err = SpotifyOAuth::from_lookup(|name| vars.get(name).map(|value| value.to_string()))
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "missing environment variables: RSPOTIFY_CLIENT_ID, RSPOTIFY_REDIRECT_URI"
+        );
+
+        vars.insert("RSPOTIFY_CLIENT_ID", "id");
+        vars.insert("RSPOTIFY_REDIRECT_URI", "http://localhost:8888/callback");
+        let oauth = SpotifyOAuth::from_lookup(|name| vars.get(name).map(|value| value.to_string()))
+            .unwrap();
+        assert_eq!(oauth.client_id, "id");
+        assert_eq!(oauth.client_secret, "secret");
+        assert_eq!(oauth.redirect_uri, "http://localhost:8888/callback");
+        assert_eq!(oauth.scope, "");
+
+        vars.insert(
+            "RSPOTIFY_SCOPES",
+            "user-read-private playlist-modify-public",
+        );
+        let oauth = SpotifyOAuth::from_lookup(|name| vars.get(name).map(|value| value.to_string()))
+            .unwrap();
+        assert_eq!(oauth.scope, "user-read-private playlist-modify-public");
+    }
+    /// Answers every token request with a new app token, and returns the
+    /// token URL
+    fn token_server() -> String {
+        use std::io::{Read, Write};
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let token_url = format!("http://{}/api/token", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0; 1024];
+                while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                    let n = stream.read(&mut buf).unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                }
+                let body = r#"{"access_token": "fresh-access", "token_type": "Bearer", "expires_in": 3600, "scope": ""}"#;
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                     Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+                .unwrap();
+            }
+        });
+        token_url
+    }
+
+    #[tokio::test]
+    async fn test_token_fetch_runs_on_token_refresh() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let seen_by_callback = Arc::clone(&seen);
+        let mut client_credential = SpotifyClientCredentials::default()
+            .client_id("id")
+            .client_secret("secret")
+            .on_token_refresh(move |token_info| {
+                seen_by_callback
+                    .lock()
+                    .unwrap()
+                    .push(token_info.access_token.clone());
+                Ok(())
+            })
+            .build();
+        client_credential.token_url = token_server();
+        assert_eq!(
+            client_credential.get_access_token().await.unwrap(),
+            "fresh-access"
+        );
+        assert_eq!(*seen.lock().unwrap(), vec!["fresh-access".to_owned()]);
+
+        // a failed persist is returned, and the token isn't used
+        let mut oauth = SpotifyOAuth::default()
+            .client_id("id")
+            .client_secret("secret")
+            .on_token_refresh(|_| Err(format_err!("couldn't persist the token")))
+            .build();
+        oauth.token_url = token_server();
+        let err = oauth
+            .refresh_access_token_without_cache("refresh-token")
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "couldn't persist the token");
+    }
+
+    #[test]
+    fn test_token_request_is_form_encoded() {
+        let mut payload = HashMap::new();
+        payload.insert("grant_type", "client_credentials");
+        let request = token_request(&Client::new(), TOKEN_URL, "id", "secret", &payload)
+            .build()
+            .unwrap();
+        assert_eq!(
+            request.headers()[CONTENT_TYPE],
+            "application/x-www-form-urlencoded"
+        );
+        assert!(request.headers().contains_key(AUTHORIZATION));
+    }
+    #[test]
+    fn test_token_request_without_secret() {
+        let mut payload = HashMap::new();
+        payload.insert("grant_type", "authorization_code");
+        let request = token_request(&Client::new(), TOKEN_URL, "id", "", &payload)
+            .build()
+            .unwrap();
+        assert!(!request.headers().contains_key(AUTHORIZATION));
+        let body = request.body().and_then(|body| body.as_bytes()).unwrap();
+        let form: HashMap<String, String> =
+            url::form_urlencoded::parse(body).into_owned().collect();
+        assert_eq!(form["client_id"], "id");
+        assert_eq!(form["grant_type"], "authorization_code");
+    }
+    #[test]
     fn test_is_scope_subset() {
         let mut needle_scope = String::from("1 2 3");
         let mut haystack_scope = String::from("1 2 3 4");
@@ -551,6 +848,96 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_on_token_refresh() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let seen_by_callback = Arc::clone(&seen);
+        let oauth = SpotifyOAuth::default()
+            .on_token_refresh(move |token_info| {
+                seen_by_callback
+                    .lock()
+                    .unwrap()
+                    .push(token_info.access_token.clone());
+                Ok(())
+            })
+            .build();
+        // clones keep the callback, and it doesn't get in the way of Debug
+        let cloned = oauth.clone();
+        assert!(format!("{:?}", cloned).contains("TokenCallback"));
+        let callback = cloned.on_token_refresh.unwrap();
+        (callback.0)(&TokenInfo::default().access_token("test-access")).unwrap();
+        assert_eq!(*seen.lock().unwrap(), vec!["test-access".to_owned()]);
+    }
+
+    #[test]
+    fn test_token_expires_at() {
+        let token_info = TokenInfo::default().expires_at(1515841743);
+        assert_eq!(
+            token_info.expires_at_datetime(),
+            "2018-01-13T11:09:03Z".parse().ok()
+        );
+        assert!(TokenInfo::default().expires_at_datetime().is_none());
+
+        let client_credential = SpotifyClientCredentials::default()
+            .client_id("this-is-my-client-id")
+            .client_secret("this-is-my-client-secret")
+            .build();
+        assert!(client_credential.token_expires_at().is_none());
+        *client_credential.app_token.lock().unwrap() = Some(token_info.clone());
+        assert_eq!(
+            client_credential.token_expires_at(),
+            token_info.expires_at_datetime()
+        );
+        let client_credential = SpotifyClientCredentials::default()
+            .token_info(TokenInfo::default().expires_at(1515845343))
+            .build();
+        assert_eq!(
+            client_credential.token_expires_at(),
+            "2018-01-13T12:09:03Z".parse().ok()
+        );
+    }
+
+    #[test]
+    fn test_blank_access_token_is_expired() {
+        let expires_at = datetime_to_timestamp(3600);
+        let token_info = TokenInfo::default()
+            .access_token("test-access")
+            .expires_at(expires_at);
+        assert!(!is_token_expired(&token_info));
+        for blank in &["", "  "] {
+            let token_info = TokenInfo::default()
+                .access_token(blank)
+                .expires_at(expires_at);
+            assert!(is_token_expired(&token_info));
+        }
+    }
+
+    #[test]
+    fn test_cached_app_token() {
+        let client_credential = SpotifyClientCredentials::default()
+            .client_id("this-is-my-client-id")
+            .client_secret("this-is-my-client-secret")
+            .build();
+        assert!(client_credential.cached_app_token().is_none());
+
+        let expired = TokenInfo::default()
+            .access_token("expired-access-token")
+            .expires_at(datetime_to_timestamp(0));
+        *client_credential.app_token.lock().unwrap() = Some(expired);
+        assert!(client_credential.cached_app_token().is_none());
+
+        let valid = TokenInfo::default()
+            .access_token("valid-access-token")
+            .expires_at(datetime_to_timestamp(3600));
+        *client_credential.app_token.lock().unwrap() = Some(valid);
+        // clones share the cached app token
+        let cloned = client_credential.clone();
+        assert_eq!(
+            cloned.cached_app_token().unwrap().access_token,
+            "valid-access-token"
+        );
+    }
+
     #[test]
     fn test_parse_response_code() {
         let mut url = String::from("http://localhost:8888/callback?code=AQD0yXvFEOvw&state=sN#_=_");
@@ -76,6 +76,24 @@ fn test_album_type_convert_from_str() {
     assert_eq!(empty_type.is_err(), true);
 }
 
+/// How `Spotify::artist_albums_all` removes duplicated albums from an
+/// artist's discography, see `util::dedup_albums`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AlbumDedup {
+    /// Keep every album as returned
+    None,
+    /// Drop albums whose ID was already seen. The same album is listed once
+    /// per relationship to the artist, e.g. both as `album` and `appears_on`.
+    Id,
+    /// Drop albums whose name (ignoring case and surrounding whitespace) and
+    /// release date were already seen. Besides the `Id` duplicates, this
+    /// catches the same release published several times under different IDs,
+    /// for instance once per market or as clean and explicit versions, at the
+    /// cost of merging genuinely different releases that share a name and a
+    /// release date.
+    NameAndReleaseDate,
+}
+
 ///  Type: ‘artist’, ‘album’,‘track’, ‘playlist’, 'show' or 'episode'
 #[derive(Clone, Serialize, Deserialize, Copy, PartialEq, Eq, Debug)]
 #[serde(rename_all = "snake_case")]
@@ -524,6 +542,9 @@ pub enum Country {
     Yemen,
     Zambia,
     Zimbabwe,
+    ///Not a country: the market of the user the access token belongs to,
+    ///only valid with a user token
+    FromToken,
 }
 impl Country {
     pub fn as_str(&self) -> &str {
@@ -777,6 +798,7 @@ impl Country {
             Country::Yemen => "YE",
             Country::Zambia => "ZM",
             Country::Zimbabwe => "ZW",
+            Country::FromToken => "from_token",
         }
     }
 }
@@ -1033,6 +1055,7 @@ impl FromStr for Country {
             "YE" => Ok(Country::Yemen),
             "ZM" => Ok(Country::Zambia),
             "ZW" => Ok(Country::Zimbabwe),
+            "from_token" => Ok(Country::FromToken),
             _ => Err(Error::new(ErrorKind::NoEnum(s.to_owned()))),
         }
     }
@@ -1041,6 +1064,8 @@ impl FromStr for Country {
 fn test_convert_country_from_str() {
     let country = Country::from_str("JP");
     assert_eq!(country.unwrap(), Country::Japan);
+    let from_token = Country::from_str("from_token");
+    assert_eq!(from_token.unwrap(), Country::FromToken);
     let unknown_country = Country::from_str("not exist enum");
     assert_eq!(unknown_country.is_err(), true);
 }
@@ -1174,3 +1199,55 @@ pub enum DeviceType {
     Automobile,
     Unknown,
 }
+
+/// Subscription level of a user: premium, free or open.
+/// `open` is returned for accounts without a full Spotify subscription, it
+/// isn't the same as `free`. Levels Spotify used before or adds later are
+/// read as `Unknown`, so that they don't fail the whole profile.
+#[derive(Clone, Serialize, Deserialize, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum SubscriptionLevel {
+    Premium,
+    Free,
+    Open,
+    #[serde(other)]
+    Unknown,
+}
+impl SubscriptionLevel {
+    pub fn as_str(&self) -> &str {
+        match *self {
+            SubscriptionLevel::Premium => "premium",
+            SubscriptionLevel::Free => "free",
+            SubscriptionLevel::Open => "open",
+            SubscriptionLevel::Unknown => "unknown",
+        }
+    }
+}
+impl FromStr for SubscriptionLevel {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "premium" => Ok(SubscriptionLevel::Premium),
+            "free" => Ok(SubscriptionLevel::Free),
+            "open" => Ok(SubscriptionLevel::Open),
+            _ => Err(Error::new(ErrorKind::NoEnum(s.to_owned()))),
+        }
+    }
+}
+#[test]
+fn test_convert_subscription_level_from_str() {
+    let level = SubscriptionLevel::from_str("open");
+    assert_eq!(level.unwrap(), SubscriptionLevel::Open);
+    let unknown_level = SubscriptionLevel::from_str("unknown_level");
+    assert!(unknown_level.is_err());
+    let level: SubscriptionLevel = serde_json::from_str(r#""basic-desktop""#).unwrap();
+    assert_eq!(level, SubscriptionLevel::Unknown);
+}
+
+/// Confirmation that a helper which can't be undone, such as
+/// `Spotify::user_unfollow_all_artists`, may go ahead, so that it isn't
+/// called by mistake.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Confirm {
+    Yes,
+}
@@ -1,15 +1,25 @@
 //! utils function
 use chrono::prelude::*;
+use futures::stream::{Stream, TryStreamExt};
+use percent_encoding::{define_encode_set, utf8_percent_encode, QUERY_ENCODE_SET};
 use rand::distributions::Alphanumeric;
 use rand::{self, Rng};
 
-use std::collections::HashMap;
+use serde::Serialize;
+use serde_json::Value;
+use std::cmp::{self, Ordering};
+use std::collections::{HashMap, HashSet};
+use std::env;
 use std::fmt::Debug;
 use std::hash::Hash;
-use std::io;
+use std::io::{self, Write};
 use std::string::ToString;
 
+use super::client::ClientError;
+use super::model::album::SimplifiedAlbum;
+use super::model::audio::{AudioFeature, AudioFeatures};
 use super::oauth2::{SpotifyOAuth, TokenInfo};
+use super::senum::AlbumDedup;
 
 /// convert datetime to unix timestampe
 pub fn datetime_to_timestamp(elapsed: u32) -> i64 {
@@ -50,6 +60,101 @@ pub fn convert_map_to_string<
     string
 }
 
+define_encode_set! {
+    /// Characters to encode in a query parameter value, e.g. with
+    /// `percent_encoding::utf8_percent_encode`: the ones of a query, and the
+    /// ones separating its parameters, so that `&` in a search doesn't end it
+    pub QUERY_VALUE_ENCODE_SET = [QUERY_ENCODE_SET] | {'&', '+', '=', '%'}
+}
+
+/// Percent-encode a query parameter value (or key) with
+/// `QUERY_VALUE_ENCODE_SET`. Values must be raw: encoding one twice turns
+/// `%20` into `%2520`.
+pub fn encode_query_value(value: &str) -> String {
+    utf8_percent_encode(value, QUERY_VALUE_ENCODE_SET).to_string()
+}
+
+/// Query string of the raw `params`, every key and value encoded once with
+/// `encode_query_value`. The parameters are sorted by key so that a request
+/// always has the same URL.
+pub fn encode_query<K: AsRef<str>, V: AsRef<str>, S: ::std::hash::BuildHasher>(
+    params: &HashMap<K, V, S>,
+) -> String {
+    let mut params: Vec<(&str, &str)> = params
+        .iter()
+        .map(|(key, value)| (key.as_ref(), value.as_ref()))
+        .collect();
+    params.sort();
+    params
+        .iter()
+        .map(|(key, value)| format!("{}={}", encode_query_value(key), encode_query_value(value)))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// How a query parameter with several values is sent, see
+/// `array_query_param`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArrayFormat {
+    /// A single comma-separated value, `ids=a,b,c`. This is what Spotify
+    /// expects for `ids`, `seed_*`, `include_groups`, `additional_types`...
+    Comma,
+    /// The key repeated for every value, `ids=a&ids=b&ids=c`
+    Repeated,
+}
+
+/// Query string fragment for a parameter with several values. The request
+/// helpers take parameters as a `HashMap<String, String>`, in which an array
+/// is a single comma-joined value, so this only needs to be used for the
+/// `Repeated` form, by putting the fragment in the url. The key and the values
+/// must be raw, they're encoded with `encode_query_value`:
+/// ```
+/// use rspotify::util::{array_query_param, ArrayFormat};
+/// let ids = ["a", "b", "c"];
+/// assert_eq!(array_query_param("ids", &ids, ArrayFormat::Comma), "ids=a,b,c");
+/// assert_eq!(
+///     array_query_param("ids", &ids, ArrayFormat::Repeated),
+///     "ids=a&ids=b&ids=c"
+/// );
+/// ```
+pub fn array_query_param<S: AsRef<str>>(key: &str, values: &[S], format: ArrayFormat) -> String {
+    match format {
+        ArrayFormat::Comma => {
+            let values: Vec<String> = values
+                .iter()
+                .map(|value| encode_query_value(value.as_ref()))
+                .collect();
+            format!("{}={}", encode_query_value(key), values.join(","))
+        }
+        ArrayFormat::Repeated => values
+            .iter()
+            .map(|value| {
+                format!(
+                    "{}={}",
+                    encode_query_value(key),
+                    encode_query_value(value.as_ref())
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("&"),
+    }
+}
+
+/// Append a query string to `url`, after the query it may already have
+pub fn append_query(url: &str, query: &str) -> String {
+    if query.is_empty() {
+        return url.to_owned();
+    }
+    let separator = if !url.contains('?') {
+        "?"
+    } else if url.ends_with('?') || url.ends_with('&') {
+        ""
+    } else {
+        "&"
+    };
+    [url, separator, query].concat()
+}
+
 /// convert query string to map, for example:
 /// convert
 /// `redirect_uri=my_uri&state=my-state&scope=test-scope`
@@ -70,6 +175,409 @@ pub fn convert_str_to_map(query_str: &mut str) -> HashMap<&str, &str> {
     map
 }
 
+/// read the given environment variables (after loading `.env`), in order.
+/// Fails with an error naming every variable that isn't set, so that all of
+/// them can be fixed at once.
+pub fn read_env_vars(names: &[&str]) -> Result<Vec<String>, failure::Error> {
+    dotenv::dotenv().ok();
+    read_vars(names, |name| env::var(name).ok())
+}
+
+/// `read_env_vars` with the variables read through `lookup`
+pub(crate) fn read_vars(
+    names: &[&str],
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Result<Vec<String>, failure::Error> {
+    let mut values = Vec::with_capacity(names.len());
+    let mut missing = Vec::new();
+    for name in names {
+        match lookup(name) {
+            Some(value) if !value.is_empty() => values.push(value),
+            _ => missing.push(*name),
+        }
+    }
+    if missing.is_empty() {
+        Ok(values)
+    } else {
+        Err(format_err!(
+            "missing environment variables: {}",
+            missing.join(", ")
+        ))
+    }
+}
+
+/// Documented bounds of the tuneable track attributes of
+/// [recommendations](https://developer.spotify.com/web-api/get-recommendations/),
+/// `None` where there's no upper bound. `loudness` has none at all.
+const RECOMMENDATION_ATTRIBUTE_RANGES: &[(&str, f64, Option<f64>)] = &[
+    ("acousticness", 0.0, Some(1.0)),
+    ("danceability", 0.0, Some(1.0)),
+    ("duration_ms", 0.0, None),
+    ("energy", 0.0, Some(1.0)),
+    ("instrumentalness", 0.0, Some(1.0)),
+    ("key", 0.0, Some(11.0)),
+    ("liveness", 0.0, Some(1.0)),
+    ("mode", 0.0, Some(1.0)),
+    ("popularity", 0.0, Some(100.0)),
+    ("speechiness", 0.0, Some(1.0)),
+    ("tempo", 0.0, None),
+    ("time_signature", 0.0, None),
+    ("valence", 0.0, Some(1.0)),
+];
+
+/// Check a `min_`, `max_` or `target_` recommendation attribute, such as
+/// `target_popularity`, against the range Spotify documents for it, so that
+/// an out of range value fails naming the attribute, with
+/// `ClientError::InvalidParameter`, instead of with a bare 400 response.
+pub fn check_recommendation_attribute(param: &str, value: &Value) -> Result<(), failure::Error> {
+    let attribute = ["min_", "max_", "target_"]
+        .iter()
+        .find_map(|prefix| param.strip_prefix(prefix))
+        .unwrap_or(param);
+    let number = match value.as_f64() {
+        Some(number) => number,
+        None => {
+            let message = format!("{} must be a number, got {}", param, value);
+            return Err(ClientError::InvalidParameter(message).into());
+        }
+    };
+    let range = RECOMMENDATION_ATTRIBUTE_RANGES
+        .iter()
+        .find(|(name, _, _)| *name == attribute);
+    match range {
+        Some((_, min, Some(max))) if number < *min || number > *max => {
+            let message = format!(
+                "{} must be between {} and {}, got {}",
+                param, min, max, number
+            );
+            Err(ClientError::InvalidParameter(message).into())
+        }
+        Some((_, min, None)) if number < *min => {
+            let message = format!("{} must be at least {}, got {}", param, min, number);
+            Err(ClientError::InvalidParameter(message).into())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// One call of the [reorder playlists tracks](https://developer.spotify.com/web-api/reorder-playlists-tracks/)
+/// endpoint: move `range_length` tracks starting at `range_start` to before
+/// the track at `insert_before`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReorderOperation {
+    pub range_start: u32,
+    pub range_length: u32,
+    pub insert_before: u32,
+}
+
+/// Decompose a reordering of a playlist into reorder operations, applied one
+/// after another. `order[i]` is the current position of the track that
+/// should end up at position `i`, so `order` must be a permutation of
+/// `0..order.len()`.
+///
+/// Positions are fixed from the start of the playlist: each operation moves
+/// the longest run of tracks that is already in the right relative order,
+/// so reordering `n` tracks takes at most `n - 1` operations.
+pub fn reorder_operations(order: &[usize]) -> Result<Vec<ReorderOperation>, failure::Error> {
+    let mut seen = vec![false; order.len()];
+    for &position in order {
+        if position >= order.len() || seen[position] {
+            bail!("{:?} is not a permutation of the playlist positions", order);
+        }
+        seen[position] = true;
+    }
+    // `current[i]` is the original position of the track now at position `i`
+    let mut current: Vec<usize> = (0..order.len()).collect();
+    let mut operations = Vec::new();
+    for i in 0..order.len() {
+        // everything before `i` is in place, so the track is after `i`
+        let start = i + current[i..].iter().position(|&p| p == order[i]).unwrap();
+        if start == i {
+            continue;
+        }
+        let mut length = 1;
+        while start + length < current.len()
+            && i + length < order.len()
+            && current[start + length] == order[i + length]
+        {
+            length += 1;
+        }
+        let moved: Vec<usize> = current.drain(start..start + length).collect();
+        current.splice(i..i, moved);
+        operations.push(ReorderOperation {
+            range_start: start as u32,
+            range_length: length as u32,
+            insert_before: i as u32,
+        });
+    }
+    Ok(operations)
+}
+
+/// Add the top-level `required` fields to a `fields` selection that doesn't
+/// select them yet, e.g. `next` to keep paging a filtered playlist:
+/// `items(track(name))` becomes `items(track(name)),next`.
+pub fn fields_with(fields: &str, required: &[&str]) -> String {
+    let mut selected = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in fields.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                selected.push(&fields[start..i]);
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    selected.push(&fields[start..]);
+    let top_level: Vec<&str> = selected
+        .iter()
+        .map(|field| field.split(&['(', '.'][..]).next().unwrap().trim())
+        .collect();
+    let mut fields = fields.to_owned();
+    for field in required {
+        if !top_level.contains(field) {
+            if !fields.is_empty() {
+                fields.push(',');
+            }
+            fields.push_str(field);
+        }
+    }
+    fields
+}
+
+/// Append to `url` the query parameters of `params` it lacks. The `next`
+/// link of a page usually repeats the parameters of the request, but not
+/// always, so they're carried over when following it.
+pub fn with_missing_params(url: &str, params: &[(&str, &str)]) -> Result<String, failure::Error> {
+    let mut url = url::Url::parse(url)?;
+    let missing: Vec<&(&str, &str)> = params
+        .iter()
+        .filter(|(key, _)| !url.query_pairs().any(|(present, _)| present == *key))
+        .collect();
+    if !missing.is_empty() {
+        let mut query = url.query_pairs_mut();
+        for (key, value) in missing {
+            query.append_pair(key, value);
+        }
+    }
+    Ok(url.into_string())
+}
+
+/// Remove duplicated albums according to `dedup`, keeping the first
+/// occurrence and the original order. Albums without an ID are never
+/// considered duplicates by `AlbumDedup::Id`.
+pub fn dedup_albums(albums: Vec<SimplifiedAlbum>, dedup: AlbumDedup) -> Vec<SimplifiedAlbum> {
+    let mut seen = HashSet::new();
+    albums
+        .into_iter()
+        .filter(|album| {
+            let key = match dedup {
+                AlbumDedup::None => return true,
+                AlbumDedup::Id => match album.id {
+                    Some(ref id) => id.clone(),
+                    None => return true,
+                },
+                AlbumDedup::NameAndReleaseDate => format!(
+                    "{}\n{}",
+                    album.name.trim().to_lowercase(),
+                    album.release_date.as_deref().unwrap_or("")
+                ),
+            };
+            seen.insert(key)
+        })
+        .collect()
+}
+
+/// Sort track IDs by the value of `feature`, lowest first, keeping the
+/// order of tracks with equal values. Tracks missing from `features` are
+/// put at the end, in their original order.
+pub fn sort_by_feature(
+    mut ids: Vec<String>,
+    features: &HashMap<String, AudioFeatures>,
+    feature: AudioFeature,
+) -> Vec<String> {
+    ids.sort_by(|a, b| {
+        let a = features.get(a).map(|features| features.value(feature));
+        let b = features.get(b).map(|features| features.value(feature));
+        match (a, b) {
+            (Some(a), Some(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }
+    });
+    ids
+}
+
+/// Keep the items before the first one `stop` returns true for, which is
+/// left out, and tell whether `stop` returned true at all.
+pub fn take_until<T, F: FnMut(&T) -> bool>(items: Vec<T>, mut stop: F) -> (Vec<T>, bool) {
+    let mut kept = Vec::with_capacity(items.len());
+    for item in items {
+        if stop(&item) {
+            return (kept, true);
+        }
+        kept.push(item);
+    }
+    (kept, false)
+}
+
+/// Write `item` to `writer` as a line of newline-delimited JSON, for exports
+/// written one item at a time
+pub fn write_ndjson<T: Serialize, W: Write>(
+    writer: &mut W,
+    item: &T,
+) -> Result<(), failure::Error> {
+    serde_json::to_writer(&mut *writer, item)?;
+    writer.write_all(b"\n")?;
+    Ok(())
+}
+
+/// Write every item of the stream `items` to `writer` with `write_ndjson`,
+/// e.g. the pages of an endpoint, so that only the items not written yet are
+/// held. Tells how many items were written, or stops at the first error of
+/// the stream or the writer, which isn't flushed.
+pub async fn export_ndjson<T, S, W>(items: S, writer: &mut W) -> Result<usize, failure::Error>
+where
+    T: Serialize,
+    S: Stream<Item = Result<T, failure::Error>>,
+    W: Write,
+{
+    futures::pin_mut!(items);
+    let mut written = 0;
+    while let Some(item) = items.try_next().await? {
+        write_ndjson(writer, &item)?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+/// Path of the value at `line` and `column` of the JSON `input`, as given
+/// by the errors of `serde_json`, e.g. `tracks.items[3].album.release_date`.
+/// An error on the closing brace of an object, such as a missing field,
+/// points at that object.
+/// Empty at the top level.
+pub(crate) fn json_path_at(input: &str, line: usize, column: usize) -> String {
+    enum Step {
+        Key(String),
+        Index(usize),
+    }
+    let line_start: usize = input
+        .split('\n')
+        .take(line.saturating_sub(1))
+        .map(|line| line.len() + 1)
+        .sum();
+    let end = cmp::min(line_start + column.saturating_sub(1), input.len());
+    let bytes = &input.as_bytes()[..end];
+    let mut path = Vec::new();
+    let mut expecting_key = false;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' => {
+                let start = i + 1;
+                i += 1;
+                while i < bytes.len() && bytes[i] != b'"' {
+                    if bytes[i] == b'\\' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+                if let (true, Some(Step::Key(key))) = (expecting_key, path.last_mut()) {
+                    let key_bytes = &bytes[start..cmp::min(i, bytes.len())];
+                    *key = String::from_utf8_lossy(key_bytes).into_owned();
+                }
+            }
+            b'{' => {
+                path.push(Step::Key(String::new()));
+                expecting_key = true;
+            }
+            b'[' => {
+                path.push(Step::Index(0));
+                expecting_key = false;
+            }
+            b'}' | b']' => {
+                path.pop();
+                expecting_key = false;
+            }
+            b':' => expecting_key = false,
+            b',' => match path.last_mut() {
+                Some(Step::Index(index)) => *index += 1,
+                Some(Step::Key(_)) => expecting_key = true,
+                None => (),
+            },
+            _ => (),
+        }
+        i += 1;
+    }
+    if let (Some(b'}'), Some(Step::Key(key))) = (input.as_bytes().get(end), path.last_mut()) {
+        key.clear();
+    }
+    let mut formatted = String::new();
+    for step in path {
+        match step {
+            Step::Key(ref key) if key.is_empty() => (),
+            Step::Key(key) => {
+                if !formatted.is_empty() {
+                    formatted.push('.');
+                }
+                formatted.push_str(&key);
+            }
+            Step::Index(index) => formatted.push_str(&format!("[{}]", index)),
+        }
+    }
+    formatted
+}
+
+/// Builds the `fields` parameter of the playlist endpoints, for example:
+/// ```
+/// use rspotify::util::FieldsBuilder;
+/// let fields = FieldsBuilder::default()
+///     .field("name")
+///     .nested(
+///         "tracks.items",
+///         FieldsBuilder::default()
+///             .field("added_by.id")
+///             .nested("track", FieldsBuilder::default().field("name").field("href")),
+///     )
+///     .build();
+/// assert_eq!(fields, "name,tracks.items(added_by.id,track(name,href))");
+/// ```
+/// Spotify silently ignores malformed field selections, so building them
+/// avoids typos in the parentheses and commas.
+#[derive(Clone, Debug, Default)]
+pub struct FieldsBuilder {
+    fields: Vec<String>,
+}
+
+impl FieldsBuilder {
+    /// select a field, use dots to reach into objects (`added_by.id`)
+    pub fn field(mut self, name: &str) -> FieldsBuilder {
+        self.fields.push(name.to_owned());
+        self
+    }
+    /// select only the given `fields` of the object or array `name`
+    pub fn nested(mut self, name: &str, fields: FieldsBuilder) -> FieldsBuilder {
+        self.fields.push(format!("{}({})", name, fields.build()));
+        self
+    }
+    pub fn build(&self) -> String {
+        self.fields.join(",")
+    }
+}
+
+/// The interactive helpers below only tell whether a token was obtained, so
+/// errors, e.g. from `on_token_refresh`, are logged
+fn log_token_error(token_info: Result<Option<TokenInfo>, failure::Error>) -> Option<TokenInfo> {
+    token_info.unwrap_or_else(|err| {
+        error!("couldn't get the token: {}", err);
+        None
+    })
+}
+
 pub fn request_token(spotify_oauth: &mut SpotifyOAuth) {
     let state = generate_random_string(16);
     let auth_url = spotify_oauth.get_authorize_url(Some(&state), None);
@@ -84,7 +592,7 @@ pub async fn process_token(
     input: &mut String,
 ) -> Option<TokenInfo> {
     match spotify_oauth.parse_response_code(input) {
-        Some(code) => spotify_oauth.get_access_token(&code).await,
+        Some(code) => log_token_error(spotify_oauth.get_access_token(&code).await),
         None => None,
     }
 }
@@ -94,14 +602,14 @@ pub async fn process_token_without_cache(
     input: &mut String,
 ) -> Option<TokenInfo> {
     match spotify_oauth.parse_response_code(input) {
-        Some(code) => spotify_oauth.get_access_token_without_cache(&code).await,
+        Some(code) => log_token_error(spotify_oauth.get_access_token_without_cache(&code).await),
         None => None,
     }
 }
 
 /// get tokenInfo by Authorization
 pub async fn get_token(spotify_oauth: &mut SpotifyOAuth) -> Option<TokenInfo> {
-    match spotify_oauth.get_cached_token().await {
+    match log_token_error(spotify_oauth.get_cached_token().await) {
         Some(token_info) => Some(token_info),
         None => {
             request_token(spotify_oauth);
@@ -128,12 +636,13 @@ pub async fn get_token_without_cache(spotify_oauth: &mut SpotifyOAuth) -> Option
 
 /// get tokenInfo by authorization and code
 pub async fn get_token_by_code(spotify_oauth: &mut SpotifyOAuth, code: &str) -> Option<TokenInfo> {
-    spotify_oauth.get_access_token(&code).await
+    log_token_error(spotify_oauth.get_access_token(&code).await)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rand::seq::SliceRandom;
     #[test]
     fn test_covert_str_to_map() {
         let mut query_url = String::from("redirect_uri=my_uri&state=my-state&scope=test-scope&");
@@ -147,6 +656,349 @@ mod tests {
         }
     }
     #[test]
+    fn test_array_query_param() {
+        let ids = vec![
+            "4iV5W9uYEdYUVa79Axb7Rh".to_owned(),
+            "1301WleyT98MSxVHPZCA6M".to_owned(),
+        ];
+        assert_eq!(
+            array_query_param("ids", &ids, ArrayFormat::Comma),
+            "ids=4iV5W9uYEdYUVa79Axb7Rh,1301WleyT98MSxVHPZCA6M"
+        );
+        assert_eq!(
+            array_query_param("ids", &ids, ArrayFormat::Repeated),
+            "ids=4iV5W9uYEdYUVa79Axb7Rh&ids=1301WleyT98MSxVHPZCA6M"
+        );
+        assert_eq!(
+            array_query_param("ids", &ids[..1], ArrayFormat::Repeated),
+            "ids=4iV5W9uYEdYUVa79Axb7Rh"
+        );
+        let none: [&str; 0] = [];
+        assert_eq!(array_query_param("ids", &none, ArrayFormat::Comma), "ids=");
+        assert_eq!(array_query_param("ids", &none, ArrayFormat::Repeated), "");
+        // every key and value is encoded, a `&` doesn't start a new parameter
+        let values = ["rock & roll", "a=b"];
+        assert_eq!(
+            array_query_param("q s", &values, ArrayFormat::Comma),
+            "q%20s=rock%20%26%20roll,a%3Db"
+        );
+        assert_eq!(
+            array_query_param("q s", &values, ArrayFormat::Repeated),
+            "q%20s=rock%20%26%20roll&q%20s=a%3Db"
+        );
+    }
+    #[test]
+    fn test_append_query() {
+        assert_eq!(append_query("tracks", "ids=a,b"), "tracks?ids=a,b");
+        assert_eq!(
+            append_query("tracks?ids=a&ids=b", "market=ES&"),
+            "tracks?ids=a&ids=b&market=ES&"
+        );
+        assert_eq!(append_query("me/tracks/?", "ids=a"), "me/tracks/?ids=a");
+        assert_eq!(append_query("tracks?ids=a", ""), "tracks?ids=a");
+    }
+    #[test]
+    fn test_fields_with() {
+        let required = ["items", "next"];
+        assert_eq!(
+            fields_with("items(track(name,href)),total", &required),
+            "items(track(name,href)),total,next"
+        );
+        assert_eq!(
+            fields_with("items.track.name,next", &required),
+            "items.track.name,next"
+        );
+        // `next` nested in another field isn't the page's `next`
+        assert_eq!(fields_with("items(next)", &required), "items(next),next");
+        assert_eq!(fields_with("", &required), "items,next");
+    }
+    #[test]
+    fn test_with_missing_params() {
+        let params = [("fields", "items(track(name)),next"), ("market", "ES")];
+        let next = with_missing_params(
+            "https://api.spotify.com/v1/playlists/59ZbFPES4DQwEjBpWHzrtC/tracks?offset=100&limit=100",
+            &params,
+        )
+        .unwrap();
+        let url = url::Url::parse(&next).unwrap();
+        let query: HashMap<_, _> = url.query_pairs().into_owned().collect();
+        assert_eq!(query["offset"], "100");
+        assert_eq!(query["fields"], "items(track(name)),next");
+        assert_eq!(query["market"], "ES");
+
+        // parameters already in the url aren't repeated
+        let next = with_missing_params(
+            "https://api.spotify.com/v1/playlists/59ZbFPES4DQwEjBpWHzrtC/tracks?offset=100&limit=100&market=ES",
+            &params,
+        )
+        .unwrap();
+        assert_eq!(next.matches("market=").count(), 1);
+        assert_eq!(next.matches("fields=").count(), 1);
+    }
+    fn album(id: &str, name: &str, release_date: &str) -> SimplifiedAlbum {
+        serde_json::from_value(serde_json::json!({
+            "album_type": "album",
+            "artists": [],
+            "external_urls": {},
+            "href": null,
+            "id": id,
+            "images": [],
+            "name": name,
+            "release_date": release_date,
+            "type": "album",
+            "uri": null
+        }))
+        .unwrap()
+    }
+    #[test]
+    fn test_dedup_albums() {
+        let albums = vec![
+            album("1", "Kind of Blue", "1959-08-17"),
+            album("2", "Kind Of Blue ", "1959-08-17"),
+            album("1", "Kind of Blue", "1959-08-17"),
+            album("3", "Kind of Blue", "1997-03-25"),
+            album("4", "Milestones", "1958"),
+        ];
+        let ids = |albums: Vec<SimplifiedAlbum>| -> Vec<String> {
+            albums.into_iter().map(|album| album.id.unwrap()).collect()
+        };
+        assert_eq!(
+            ids(dedup_albums(albums.clone(), AlbumDedup::None)),
+            vec!["1", "2", "1", "3", "4"]
+        );
+        assert_eq!(
+            ids(dedup_albums(albums.clone(), AlbumDedup::Id)),
+            vec!["1", "2", "3", "4"]
+        );
+        assert_eq!(
+            ids(dedup_albums(albums, AlbumDedup::NameAndReleaseDate)),
+            vec!["1", "3", "4"]
+        );
+    }
+    #[test]
+    fn test_read_env_vars() {
+        env::set_var("RSPOTIFY_TEST_READ_ENV_A", "a");
+        env::set_var("RSPOTIFY_TEST_READ_ENV_B", "b");
+        let values = read_env_vars(&["RSPOTIFY_TEST_READ_ENV_B", "RSPOTIFY_TEST_READ_ENV_A"]);
+        assert_eq!(values.unwrap(), vec!["b", "a"]);
+
+        let err = read_env_vars(&[
+            "RSPOTIFY_TEST_READ_ENV_MISSING_1",
+            "RSPOTIFY_TEST_READ_ENV_A",
+            "RSPOTIFY_TEST_READ_ENV_MISSING_2",
+        ])
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "missing environment variables: RSPOTIFY_TEST_READ_ENV_MISSING_1, RSPOTIFY_TEST_READ_ENV_MISSING_2"
+        );
+    }
+    #[test]
+    fn test_fields_builder() {
+        assert_eq!(FieldsBuilder::default().build(), "");
+        let fields = FieldsBuilder::default()
+            .field("total")
+            .nested(
+                "items",
+                FieldsBuilder::default().nested(
+                    "track",
+                    FieldsBuilder::default()
+                        .field("name")
+                        .nested("album", FieldsBuilder::default().field("name")),
+                ),
+            )
+            .build();
+        assert_eq!(fields, "total,items(track(name,album(name)))");
+    }
+    /// apply operations the way Spotify does, `insert_before` being a
+    /// position before the range is removed
+    fn apply_reorder_operations(operations: &[ReorderOperation], len: usize) -> Vec<usize> {
+        let mut tracks: Vec<usize> = (0..len).collect();
+        for operation in operations {
+            let start = operation.range_start as usize;
+            let end = start + operation.range_length as usize;
+            let insert_before = operation.insert_before as usize;
+            let moved: Vec<usize> = tracks.drain(start..end).collect();
+            let insert_at = if insert_before > start {
+                insert_before - moved.len()
+            } else {
+                insert_before
+            };
+            tracks.splice(insert_at..insert_at, moved);
+        }
+        tracks
+    }
+
+    #[test]
+    fn test_reorder_operations() {
+        assert!(reorder_operations(&[]).unwrap().is_empty());
+        assert!(reorder_operations(&[0, 1, 2]).unwrap().is_empty());
+
+        // a contiguous block is moved in a single operation
+        let operations = reorder_operations(&[3, 4, 5, 0, 1, 2]).unwrap();
+        assert_eq!(
+            operations,
+            vec![ReorderOperation {
+                range_start: 3,
+                range_length: 3,
+                insert_before: 0,
+            }]
+        );
+
+        // non-contiguous selection moved to the front
+        let order = [1, 3, 6, 0, 2, 4, 5, 7];
+        let operations = reorder_operations(&order).unwrap();
+        assert_eq!(apply_reorder_operations(&operations, order.len()), order);
+
+        let mut order: Vec<usize> = (0..200).collect();
+        for _ in 0..20 {
+            order.shuffle(&mut rand::thread_rng());
+            let operations = reorder_operations(&order).unwrap();
+            assert!(operations.len() < order.len());
+            assert_eq!(apply_reorder_operations(&operations, order.len()), order);
+        }
+
+        assert!(reorder_operations(&[0, 0, 1]).is_err());
+        assert!(reorder_operations(&[0, 3, 1]).is_err());
+    }
+    #[test]
+    fn test_check_recommendation_attribute() {
+        assert!(check_recommendation_attribute("target_popularity", &json!(80)).is_ok());
+        assert!(check_recommendation_attribute("min_danceability", &json!(0.4)).is_ok());
+        assert!(check_recommendation_attribute("max_tempo", &json!(180.5)).is_ok());
+        assert!(check_recommendation_attribute("target_loudness", &json!(-8.5)).is_ok());
+        let message =
+            |param: &str, value: Value| match check_recommendation_attribute(param, &value)
+                .unwrap_err()
+                .downcast::<ClientError>()
+            {
+                Ok(ClientError::InvalidParameter(message)) => message,
+                other => panic!("expected InvalidParameter, got {:?}", other),
+            };
+        assert_eq!(
+            message("target_popularity", json!(101)),
+            "target_popularity must be between 0 and 100, got 101"
+        );
+        assert_eq!(
+            message("min_tempo", json!(-1)),
+            "min_tempo must be at least 0, got -1"
+        );
+        assert_eq!(
+            message("max_danceability", json!(1.5)),
+            "max_danceability must be between 0 and 1, got 1.5"
+        );
+        assert_eq!(
+            message("target_energy", json!("high")),
+            "target_energy must be a number, got \"high\""
+        );
+    }
+    #[test]
+    fn test_sort_by_feature() {
+        let features: HashMap<String, AudioFeatures> =
+            [("fast", 170.0), ("slow", 70.0), ("mid", 120.0)]
+                .iter()
+                .map(|&(id, tempo)| {
+                    let features = json!({
+                        "acousticness": 0.0, "analysis_url": "", "danceability": 0.0,
+                        "duration_ms": 0, "energy": 0.0, "id": id, "instrumentalness": 0.0,
+                        "key": 0, "liveness": 0.0, "loudness": 0.0, "mode": 0,
+                        "speechiness": 0.0, "tempo": tempo, "time_signature": 4,
+                        "track_href": "", "type": "audio_features", "uri": "", "valence": 0.0
+                    });
+                    (id.to_owned(), serde_json::from_value(features).unwrap())
+                })
+                .collect();
+        let ids = ["unknown", "fast", "slow", "mid"]
+            .iter()
+            .map(|id| id.to_string())
+            .collect();
+        assert_eq!(
+            sort_by_feature(ids, &features, AudioFeature::Tempo),
+            vec!["slow", "mid", "fast", "unknown"]
+        );
+    }
+    #[test]
+    fn test_query_value_encode_set() {
+        use percent_encoding::utf8_percent_encode;
+        let value =
+            utf8_percent_encode(r#"artist:"Simon & Garfunkel" 100%"#, QUERY_VALUE_ENCODE_SET);
+        assert_eq!(
+            value.to_string(),
+            "artist:%22Simon%20%26%20Garfunkel%22%20100%25"
+        );
+    }
+    #[test]
+    fn test_encode_query() {
+        let mut params = HashMap::new();
+        params.insert("q", "Simon & Garfunkel +1 #2 100% é");
+        params.insert("fields", "items(track(name,id)),next");
+        params.insert("market", "ES");
+        assert_eq!(
+            encode_query(&params),
+            "fields=items(track(name,id)),next&market=ES\
+             &q=Simon%20%26%20Garfunkel%20%2B1%20%232%20100%25%20%C3%A9"
+        );
+        assert_eq!(encode_query(&HashMap::<String, String>::new()), "");
+        // already encoded values are encoded again, they must be raw
+        assert_eq!(encode_query_value("a%20b"), "a%2520b");
+    }
+    #[test]
+    fn test_take_until() {
+        assert_eq!(
+            take_until(vec![1, 2, 3, 4], |&n| n == 3),
+            (vec![1, 2], true)
+        );
+        assert_eq!(take_until(vec![1, 2], |&n| n == 3), (vec![1, 2], false));
+        assert_eq!(take_until(vec![3, 4], |&n| n == 3), (vec![], true));
+    }
+    #[tokio::test]
+    async fn test_export_ndjson() {
+        let items = futures::stream::iter(vec![Ok(1), Ok(2)]);
+        let mut out = Vec::new();
+        assert_eq!(export_ndjson(items, &mut out).await.unwrap(), 2);
+        assert_eq!(out, b"1\n2\n");
+        // the items after an error aren't written
+        let items = futures::stream::iter(vec![Ok(1), Err(format_err!("page")), Ok(3)]);
+        let mut out = Vec::new();
+        assert!(export_ndjson(items, &mut out).await.is_err());
+        assert_eq!(out, b"1\n");
+    }
+    #[test]
+    fn test_write_ndjson() {
+        let mut out = Vec::new();
+        write_ndjson(&mut out, &serde_json::json!({"id": "a\nb"})).unwrap();
+        write_ndjson(&mut out, &vec![1, 2]).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "{\"id\":\"a\\nb\"}\n[1,2]\n"
+        );
+    }
+    #[test]
+    fn test_json_path_at() {
+        #[derive(Debug, Deserialize)]
+        struct Album {
+            #[allow(dead_code)]
+            release_date: String,
+        }
+        #[derive(Debug, Deserialize)]
+        struct Track {
+            #[allow(dead_code)]
+            album: Album,
+        }
+        let path_of_error = |input: &str| {
+            let e = serde_json::from_str::<Vec<Track>>(input).unwrap_err();
+            json_path_at(input, e.line(), e.column())
+        };
+        let wrong_type = r#"[
+            {"album": {"release_date": "2020"}},
+            {"album": {"release_date": 2020, "name": "x\"y"}}
+        ]"#;
+        assert_eq!(path_of_error(wrong_type), "[1].album.release_date");
+        let missing = r#"[{"album": {"name": "x"}}]"#;
+        assert_eq!(path_of_error(missing), "[0].album");
+        assert_eq!(json_path_at("{}", 0, 0), "");
+    }
+    #[test]
     fn test_convert_map_to_string() {
         let mut map = HashMap::new();
         map.insert("redirect_uri", "my_uri");
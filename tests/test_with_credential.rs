@@ -3,10 +3,13 @@ extern crate rspotify;
 extern crate lazy_static;
 
 use rspotify::client::Spotify;
+use rspotify::model::album::FullAlbum;
 
 use rspotify::oauth2::SpotifyClientCredentials;
-use rspotify::senum::{AlbumType, Country};
+use rspotify::senum::{AlbumDedup, AlbumType, Country, SearchType};
 
+use std::collections::HashMap;
+use std::ops::ControlFlow;
 use std::sync::Mutex;
 
 lazy_static! {
@@ -54,6 +57,148 @@ async fn test_album_tracks() {
     assert!(tracks.is_ok());
 }
 
+#[tokio::test]
+async fn test_album_tracks_all() {
+    let spotify = Spotify::default()
+        .client_credentials_manager(CLIENT_CREDENTIAL.lock().unwrap().clone())
+        .build();
+    let birdy_uri = "spotify:album:6akEvsycLGftJxYudPjmqK";
+    let tracks = spotify
+        .album_tracks_all(birdy_uri, Some(Country::Spain))
+        .await
+        .unwrap();
+    let album = spotify.album(birdy_uri).await.unwrap();
+    assert_eq!(tracks.len() as u32, album.tracks.total);
+    assert!(tracks.iter().all(|track| track.is_playable.is_some()));
+}
+
+#[tokio::test]
+async fn test_album_tracks_full() {
+    let spotify = Spotify::default()
+        .client_credentials_manager(CLIENT_CREDENTIAL.lock().unwrap().clone())
+        .build();
+    let birdy_uri = "spotify:album:6akEvsycLGftJxYudPjmqK";
+    let tracks = spotify.album_tracks_full(birdy_uri, None).await.unwrap();
+    let album = spotify.album(birdy_uri).await.unwrap();
+    assert_eq!(tracks.len() as u32, album.tracks.total);
+    assert!(tracks.iter().all(|track| track.album.name == album.name));
+}
+
+#[tokio::test]
+async fn test_artist_albums_all() {
+    let spotify = Spotify::default()
+        .client_credentials_manager(CLIENT_CREDENTIAL.lock().unwrap().clone())
+        .build();
+    let birdy_uri = "spotify:artist:2WX2uTcsvV5OnS0inACecP";
+    let groups = [AlbumType::Album, AlbumType::AppearsOn];
+    let albums = spotify
+        .artist_albums_all(
+            birdy_uri,
+            &groups,
+            Some(Country::UnitedStates),
+            AlbumDedup::None,
+        )
+        .await
+        .unwrap();
+    let deduped = spotify
+        .artist_albums_all(
+            birdy_uri,
+            &groups,
+            Some(Country::UnitedStates),
+            AlbumDedup::Id,
+        )
+        .await
+        .unwrap();
+    assert!(!deduped.is_empty());
+    assert!(deduped.len() <= albums.len());
+    let mut ids: Vec<_> = deduped.iter().map(|album| album.id.clone()).collect();
+    ids.sort();
+    ids.dedup();
+    assert_eq!(ids.len(), deduped.len());
+}
+
+#[tokio::test]
+async fn test_get_raw() {
+    let spotify = Spotify::default()
+        .client_credentials_manager(CLIENT_CREDENTIAL.lock().unwrap().clone())
+        .build();
+    let mut params = HashMap::new();
+    params.insert("limit".to_owned(), "2".to_owned());
+    let page = spotify
+        .get_raw("/albums/6akEvsycLGftJxYudPjmqK/tracks", &mut params)
+        .await
+        .unwrap();
+    assert_eq!(page["items"].as_array().unwrap().len(), 2);
+    // the `next` link is absolute, and is followed as is
+    let next = page["next"].as_str().unwrap();
+    let next_page = spotify.get_raw(next, &mut HashMap::new()).await.unwrap();
+    assert_eq!(next_page["offset"], 2);
+}
+
+#[tokio::test]
+async fn test_search_all_stops_at_offset_cap() {
+    let spotify = Spotify::default()
+        .client_credentials_manager(CLIENT_CREDENTIAL.lock().unwrap().clone())
+        .build();
+    let all = spotify
+        .search_all("love", SearchType::Track, Some(Country::UnitedStates), None)
+        .await
+        .unwrap();
+    assert!(all.result.len() <= 1000);
+    assert!(all.result.total() > 1000);
+    assert!(all.truncated);
+}
+
+#[tokio::test]
+async fn test_search_artists() {
+    let spotify = Spotify::default()
+        .client_credentials_manager(CLIENT_CREDENTIAL.lock().unwrap().clone())
+        .build();
+    let artists = spotify
+        .search_artists("tania bowra", 10, 0, Some(Country::UnitedStates))
+        .await
+        .unwrap();
+    assert!(artists
+        .items
+        .iter()
+        .any(|artist| artist.name == "Tania Bowra"));
+}
+
+#[tokio::test]
+async fn test_artist_page() {
+    let spotify = Spotify::default()
+        .client_credentials_manager(CLIENT_CREDENTIAL.lock().unwrap().clone())
+        .build();
+    let birdy_uri = "spotify:artist:2WX2uTcsvV5OnS0inACecP";
+    let page = spotify
+        .artist_page(birdy_uri, Some(Country::UnitedStates))
+        .await
+        .unwrap();
+    assert_eq!(page.artist.id, "2WX2uTcsvV5OnS0inACecP");
+    assert!(!page.top_tracks.unwrap().is_empty());
+    assert!(!page.albums.unwrap().items.is_empty());
+}
+
+#[tokio::test]
+async fn test_batch_get() {
+    let spotify = Spotify::default()
+        .client_credentials_manager(CLIENT_CREDENTIAL.lock().unwrap().clone())
+        .build();
+    let ids = [
+        "41MnTivkwTO3UUJ8DrqEJJ",
+        "6JWc4iAiJ9FjyK0B59ABb4",
+        "6UXCm6bOO4gFlDQZV5yL37",
+    ];
+    let albums = spotify
+        .batch_get::<FullAlbum>("albums", &ids, 2, &HashMap::new())
+        .await
+        .unwrap();
+    assert_eq!(albums.len(), 3);
+    for (id, album) in ids.iter().zip(albums) {
+        assert_eq!(album.unwrap().id, *id);
+    }
+}
+
 #[tokio::test]
 async fn test_artist_related_artists() {
     let spotify = Spotify::default()
@@ -152,6 +297,19 @@ async fn test_audios_features() {
     assert!(features.is_ok())
 }
 
+#[tokio::test]
+async fn test_tracks_features_map() {
+    let spotify = Spotify::default()
+        .client_credentials_manager(CLIENT_CREDENTIAL.lock().unwrap().clone())
+        .build();
+    let tracks_ids = vec![
+        String::from("spotify:track:4JpKVNYnVcJ8tuMKjAj50A"),
+        String::from("spotify:track:24JygzOLM0EmRQeGtFcIcG"),
+    ];
+    let features = spotify.tracks_features_map(&tracks_ids).await.unwrap();
+    assert!(features.contains_key("4JpKVNYnVcJ8tuMKjAj50A"));
+}
+
 #[tokio::test]
 async fn test_user() {
     let spotify = Spotify::default()
@@ -205,6 +363,100 @@ async fn test_fake_playlist() {
     assert!(!playlist.is_ok());
 }
 
+#[tokio::test]
+async fn test_playlist_tracks() {
+    let spotify = Spotify::default()
+        .client_credentials_manager(CLIENT_CREDENTIAL.lock().unwrap().clone())
+        .build();
+
+    let tracks = spotify
+        .playlist_tracks(
+            "37i9dQZF1DZ06evO45P0Eo",
+            None,
+            Some(2),
+            None,
+            Some(Country::Spain),
+        )
+        .await;
+    assert!(tracks.is_ok());
+}
+
+#[tokio::test]
+async fn test_playlist_track_count() {
+    let spotify = Spotify::default()
+        .client_credentials_manager(CLIENT_CREDENTIAL.lock().unwrap().clone())
+        .build();
+
+    let count = spotify.playlist_track_count("37i9dQZF1DZ06evO45P0Eo").await;
+    assert!(count.is_ok());
+}
+
+#[tokio::test]
+async fn test_playlist_tracks_all_keeps_fields() {
+    let spotify = Spotify::default()
+        .client_credentials_manager(CLIENT_CREDENTIAL.lock().unwrap().clone())
+        .build();
+
+    let playlist_id = "37i9dQZF1DZ06evO45P0Eo";
+    let items: Vec<serde_json::Value> = spotify
+        .playlist_tracks_all(playlist_id, Some("items(track(name))"), None)
+        .await
+        .unwrap();
+    let count = spotify.playlist_track_count(playlist_id).await.unwrap();
+    assert_eq!(items.len() as u32, count);
+    // every page was filtered, so no item has more than the track name
+    for item in items {
+        assert_eq!(item.as_object().unwrap().len(), 1);
+        assert_eq!(item["track"].as_object().unwrap().len(), 1);
+        assert!(item["track"]["name"].is_string());
+    }
+}
+
+#[tokio::test]
+async fn test_for_each_playlist_track_stops_early() {
+    let spotify = Spotify::default()
+        .client_credentials_manager(CLIENT_CREDENTIAL.lock().unwrap().clone())
+        .build();
+
+    let mut names = Vec::new();
+    spotify
+        .for_each_playlist_track(
+            "37i9dQZF1DZ06evO45P0Eo",
+            Some("items(track(name))"),
+            None,
+            |item: serde_json::Value| {
+                names.push(item["track"]["name"].as_str().unwrap_or("").to_owned());
+                if names.len() == 3 {
+                    ControlFlow::Break(())
+                } else {
+                    ControlFlow::Continue(())
+                }
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(names.len(), 3);
+}
+
+#[tokio::test]
+async fn test_playlist_tracks_snapshot() {
+    let spotify = Spotify::default()
+        .client_credentials_manager(CLIENT_CREDENTIAL.lock().unwrap().clone())
+        .build();
+
+    let playlist_id = "37i9dQZF1DZ06evO45P0Eo";
+    let snapshot = spotify
+        .playlist_tracks_snapshot(playlist_id, None)
+        .await
+        .unwrap();
+    let count = spotify.playlist_track_count(playlist_id).await.unwrap();
+    assert_eq!(snapshot.tracks.len() as u32, count);
+    let changed = spotify
+        .playlist_changed_since(playlist_id, &snapshot.snapshot_id)
+        .await;
+    assert!(changed.is_ok());
+}
+
 #[tokio::test]
 async fn test_add_queue() {
     let spotify = Spotify::default()
@@ -215,3 +467,30 @@ async fn test_add_queue() {
     let res = spotify.add_item_to_queue(birdy_uri, None).await;
     assert!(!res.is_ok());
 }
+
+#[tokio::test]
+async fn test_fetch_image() {
+    let spotify = Spotify::default()
+        .client_credentials_manager(CLIENT_CREDENTIAL.lock().unwrap().clone())
+        .build();
+    let birdy_uri = "spotify:album:0sNOF9WDwhWunNAHPD3Baj";
+    let album = spotify.album(birdy_uri).await.unwrap();
+    let bytes = spotify.fetch_image(&album.images[0].url).await.unwrap();
+    assert!(!bytes.is_empty());
+}
+
+#[tokio::test]
+async fn test_export_playlist_tracks_ndjson() {
+    let spotify = Spotify::default()
+        .client_credentials_manager(CLIENT_CREDENTIAL.lock().unwrap().clone())
+        .build();
+    let playlist_id = "37i9dQZF1DZ06evO45P0Eo";
+    let mut out = Vec::new();
+    let written = spotify
+        .export_playlist_tracks_ndjson(playlist_id, None, &mut out)
+        .await
+        .unwrap();
+    let count = spotify.playlist_track_count(playlist_id).await.unwrap();
+    assert_eq!(written as u32, count);
+    assert_eq!(String::from_utf8(out).unwrap().lines().count(), written);
+}
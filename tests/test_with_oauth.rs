@@ -7,6 +7,7 @@ use serde_json::map::Map;
 
 use rspotify::client::Spotify;
 use rspotify::model::offset::for_position;
+use rspotify::model::playing::TimeLimits;
 use rspotify::oauth2::{SpotifyClientCredentials, SpotifyOAuth};
 use rspotify::senum::{Country, RepeatState, SearchType, TimeRange};
 use rspotify::util::get_token;
@@ -56,6 +57,70 @@ async fn test_current_playback() {
         None => assert!(false),
     };
 }
+
+#[tokio::test]
+#[ignore]
+async fn test_playback_snapshot() {
+    let mut oauth = SpotifyOAuth::default()
+        .scope("user-read-playback-state")
+        .build();
+    match get_token(&mut oauth).await {
+        Some(token_info) => {
+            let client_credential = SpotifyClientCredentials::default()
+                .token_info(token_info)
+                .build();
+            let spotify = Spotify::default()
+                .client_credentials_manager(client_credential)
+                .build();
+            let snapshot = spotify.playback_snapshot(true).await;
+            assert!(snapshot.is_ok());
+            assert!(snapshot.unwrap().devices.is_some());
+        }
+        None => assert!(false),
+    };
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_recommendations_from_current_playback() {
+    let mut oauth = SpotifyOAuth::default()
+        .scope("user-read-playback-state")
+        .build();
+    match get_token(&mut oauth).await {
+        Some(token_info) => {
+            let client_credential = SpotifyClientCredentials::default()
+                .token_info(token_info)
+                .build();
+            let spotify = Spotify::default()
+                .client_credentials_manager(client_credential)
+                .build();
+            let recommendations = spotify.recommendations_from_current_playback(10).await;
+            assert!(recommendations.is_ok());
+        }
+        None => assert!(false),
+    };
+}
+#[tokio::test]
+#[ignore]
+async fn test_recommendations_from_top_artists() {
+    let mut oauth = SpotifyOAuth::default().scope("user-top-read").build();
+    match get_token(&mut oauth).await {
+        Some(token_info) => {
+            let client_credential = SpotifyClientCredentials::default()
+                .token_info(token_info)
+                .build();
+            let spotify = Spotify::default()
+                .client_credentials_manager(client_credential)
+                .build();
+            let recommendations = spotify
+                .recommendations_from_top_artists(TimeRange::LongTerm, 10)
+                .await
+                .unwrap();
+            assert!(recommendations.seeds.len() <= 5);
+        }
+        None => assert!(false),
+    };
+}
 #[tokio::test]
 #[ignore]
 async fn test_current_playing() {
@@ -139,6 +204,29 @@ async fn test_current_user_playlists() {
 }
 #[tokio::test]
 #[ignore]
+async fn test_current_user_owned_playlists_all() {
+    let mut oauth = SpotifyOAuth::default()
+        .scope("playlist-read-private")
+        .build();
+    match get_token(&mut oauth).await {
+        Some(token_info) => {
+            let client_credential = SpotifyClientCredentials::default()
+                .token_info(token_info)
+                .build();
+            let spotify = Spotify::default()
+                .client_credentials_manager(client_credential)
+                .build();
+            let user_id = spotify.current_user_id().await.unwrap();
+            let all = spotify.current_user_playlists_all().await.unwrap();
+            let owned = spotify.current_user_owned_playlists_all().await.unwrap();
+            assert!(owned.len() <= all.len());
+            assert!(owned.iter().all(|playlist| playlist.owner.id == user_id));
+        }
+        None => assert!(false),
+    };
+}
+#[tokio::test]
+#[ignore]
 async fn test_current_user_recently_played() {
     let mut oauth = SpotifyOAuth::default()
         .scope("user-read-recently-played")
@@ -151,8 +239,40 @@ async fn test_current_user_recently_played() {
             let spotify = Spotify::default()
                 .client_credentials_manager(client_credential)
                 .build();
-            let history = spotify.current_user_recently_played(10).await;
+            let history = spotify.current_user_recently_played(10, None).await;
             assert!(history.is_ok());
+            let since = Utc::now() - chrono::Duration::days(1);
+            let history = spotify
+                .current_user_recently_played(10, Some(TimeLimits::After(since)))
+                .await
+                .unwrap();
+            assert!(history.items.iter().all(|play| play.played_at > since));
+        }
+        None => assert!(false),
+    };
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_current_user_recently_played_within() {
+    let mut oauth = SpotifyOAuth::default()
+        .scope("user-read-recently-played")
+        .build();
+    match get_token(&mut oauth).await {
+        Some(token_info) => {
+            let client_credential = SpotifyClientCredentials::default()
+                .token_info(token_info)
+                .build();
+            let spotify = Spotify::default()
+                .client_credentials_manager(client_credential)
+                .build();
+            let week = std::time::Duration::from_secs(7 * 24 * 60 * 60);
+            let plays = spotify
+                .current_user_recently_played_within(week)
+                .await
+                .unwrap();
+            let since = Utc::now() - chrono::Duration::weeks(1);
+            assert!(plays.iter().all(|play| play.played_at >= since));
         }
         None => assert!(false),
     };
@@ -249,6 +369,67 @@ async fn test_current_user_saved_tracks_add() {
     };
 }
 
+#[tokio::test]
+#[ignore]
+async fn test_current_user_saved_tracks_add_reporting() {
+    let mut oauth = SpotifyOAuth::default()
+        .scope("user-library-read user-library-modify")
+        .build();
+    match get_token(&mut oauth).await {
+        Some(token_info) => {
+            let client_credential = SpotifyClientCredentials::default()
+                .token_info(token_info)
+                .build();
+            let spotify = Spotify::default()
+                .client_credentials_manager(client_credential)
+                .build();
+            let saved = String::from("4iV5W9uYEdYUVa79Axb7Rh");
+            let unsaved = String::from("1301WleyT98MSxVHPZCA6M");
+            spotify
+                .current_user_saved_tracks_add(std::slice::from_ref(&saved))
+                .await
+                .unwrap();
+            spotify
+                .current_user_saved_tracks_delete(std::slice::from_ref(&unsaved))
+                .await
+                .unwrap();
+            let report = spotify
+                .current_user_saved_tracks_add_reporting(&[
+                    format!("spotify:track:{}", saved),
+                    unsaved.clone(),
+                ])
+                .await
+                .unwrap();
+            assert_eq!(report.already_present, vec![saved]);
+            assert_eq!(report.newly_added, vec![unsaved]);
+        }
+        None => assert!(false),
+    };
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_set_saved() {
+    let mut oauth = SpotifyOAuth::default()
+        .scope("user-library-read user-library-modify")
+        .build();
+    match get_token(&mut oauth).await {
+        Some(token_info) => {
+            let client_credential = SpotifyClientCredentials::default()
+                .token_info(token_info)
+                .build();
+            let spotify = Spotify::default()
+                .client_credentials_manager(client_credential)
+                .build();
+            let track_id = "spotify:track:4iV5W9uYEdYUVa79Axb7Rh";
+            assert!(spotify.set_saved(track_id, true).await.is_ok());
+            // already saved, so nothing to write
+            assert!(!spotify.set_saved(track_id, true).await.unwrap());
+        }
+        None => assert!(false),
+    };
+}
+
 #[tokio::test]
 #[ignore]
 async fn test_current_user_saved_tracks_contains() {
@@ -275,6 +456,32 @@ async fn test_current_user_saved_tracks_contains() {
     };
 }
 
+#[tokio::test]
+#[ignore]
+async fn test_current_user_saved_tracks_until() {
+    let mut oauth = SpotifyOAuth::default().scope("user-library-read").build();
+    match get_token(&mut oauth).await {
+        Some(token_info) => {
+            let client_credential = SpotifyClientCredentials::default()
+                .token_info(token_info)
+                .build();
+            let spotify = Spotify::default()
+                .client_credentials_manager(client_credential)
+                .build();
+            let mut seen = 0;
+            let tracks = spotify
+                .current_user_saved_tracks_until(|_| {
+                    seen += 1;
+                    seen > 3
+                })
+                .await
+                .unwrap();
+            assert!(tracks.len() <= 3);
+        }
+        None => assert!(false),
+    };
+}
+
 #[tokio::test]
 #[ignore]
 async fn test_current_user_saved_tracks_delete() {
@@ -318,6 +525,25 @@ async fn test_current_user_saved_tracks() {
 }
 #[tokio::test]
 #[ignore]
+async fn test_current_user_saved_tracks_count() {
+    let mut oauth = SpotifyOAuth::default().scope("user-library-read").build();
+    match get_token(&mut oauth).await {
+        Some(token_info) => {
+            let client_credential = SpotifyClientCredentials::default()
+                .token_info(token_info)
+                .build();
+            let spotify = Spotify::default()
+                .client_credentials_manager(client_credential)
+                .build();
+            let count = spotify.current_user_saved_tracks_count().await.unwrap();
+            let tracks = spotify.current_user_saved_tracks(1, 0).await.unwrap();
+            assert_eq!(count, tracks.total);
+        }
+        None => assert!(false),
+    }
+}
+#[tokio::test]
+#[ignore]
 async fn test_current_user_top_artists() {
     let mut oauth = SpotifyOAuth::default().scope("user-top-read").build();
     match get_token(&mut oauth).await {
@@ -337,6 +563,25 @@ async fn test_current_user_top_artists() {
     };
 }
 
+#[tokio::test]
+#[ignore]
+async fn test_top_genres() {
+    let mut oauth = SpotifyOAuth::default().scope("user-top-read").build();
+    match get_token(&mut oauth).await {
+        Some(token_info) => {
+            let client_credential = SpotifyClientCredentials::default()
+                .token_info(token_info)
+                .build();
+            let spotify = Spotify::default()
+                .client_credentials_manager(client_credential)
+                .build();
+            let genres = spotify.top_genres(TimeRange::LongTerm, 50).await;
+            assert!(genres.is_ok());
+        }
+        None => assert!(false),
+    };
+}
+
 #[tokio::test]
 #[ignore]
 async fn test_current_user_top_tracks() {
@@ -443,6 +688,33 @@ async fn test_new_releases() {
     };
 }
 
+#[tokio::test]
+#[ignore]
+async fn test_new_releases_all() {
+    let mut oauth = SpotifyOAuth::default().scope("user-follow-read").build();
+    match get_token(&mut oauth).await {
+        Some(token_info) => {
+            let client_credential = SpotifyClientCredentials::default()
+                .token_info(token_info)
+                .build();
+            let spotify = Spotify::default()
+                .client_credentials_manager(client_credential)
+                .build();
+
+            let first = spotify
+                .new_releases(Some(Country::Sweden), 1, 0)
+                .await
+                .unwrap();
+            let albums = spotify
+                .new_releases_all(Some(Country::Sweden))
+                .await
+                .unwrap();
+            assert_eq!(albums.len() as u32, first.albums.total);
+        }
+        None => assert!(false),
+    };
+}
+
 #[tokio::test]
 #[ignore]
 async fn test_next_playback() {
@@ -542,6 +814,39 @@ async fn test_recommendations() {
     };
 }
 
+#[tokio::test]
+#[ignore]
+async fn test_recommendations_to_playlist() {
+    let mut oauth = SpotifyOAuth::default()
+        .scope("user-read-private playlist-modify-public")
+        .build();
+    match get_token(&mut oauth).await {
+        Some(token_info) => {
+            let client_credential = SpotifyClientCredentials::default()
+                .token_info(token_info)
+                .build();
+            let spotify = Spotify::default()
+                .client_credentials_manager(client_credential)
+                .build();
+            let seed_tracks = vec!["0c6xIDDpzE81m2q797ordA".to_owned()];
+            let playlist = spotify
+                .recommendations_to_playlist(
+                    "recommendations",
+                    None,
+                    None,
+                    Some(seed_tracks),
+                    10,
+                    &Map::new(),
+                )
+                .await
+                .unwrap();
+            assert!(playlist.tracks.total <= 10);
+            assert_eq!(playlist.tracks.total as usize, playlist.tracks.items.len());
+        }
+        None => assert!(false),
+    };
+}
+
 #[tokio::test]
 #[ignore]
 async fn test_repeat() {
@@ -740,6 +1045,54 @@ async fn test_start_playback() {
 }
 #[tokio::test]
 #[ignore]
+async fn test_start_playback_with_episodes() {
+    let mut oauth = SpotifyOAuth::default()
+        .scope("user-modify-playback-state")
+        .build();
+    match get_token(&mut oauth).await {
+        Some(token_info) => {
+            let client_credential = SpotifyClientCredentials::default()
+                .token_info(token_info)
+                .build();
+            let spotify = Spotify::default()
+                .client_credentials_manager(client_credential)
+                .build();
+            let device_id = String::from("74ASZWbe4lXaubB36ztrGX");
+            let uris = vec![
+                "spotify:episode:512ojhOuo1ktJprKbVcKyQ".to_owned(),
+                "spotify:track:4iV5W9uYEdYUVa79Axb7Rh".to_owned(),
+            ];
+            let result = spotify
+                .start_playback(Some(device_id), None, Some(uris), for_position(0), None)
+                .await;
+            assert!(result.is_ok());
+        }
+        None => assert!(false),
+    };
+}
+#[tokio::test]
+#[ignore]
+async fn test_play_on_any_available() {
+    let mut oauth = SpotifyOAuth::default()
+        .scope("user-read-playback-state user-modify-playback-state")
+        .build();
+    match get_token(&mut oauth).await {
+        Some(token_info) => {
+            let client_credential = SpotifyClientCredentials::default()
+                .token_info(token_info)
+                .build();
+            let spotify = Spotify::default()
+                .client_credentials_manager(client_credential)
+                .build();
+            let context_uri = String::from("spotify:album:6akEvsycLGftJxYudPjmqK");
+            let result = spotify.play_on_any_available(Some(context_uri)).await;
+            assert!(result.is_ok());
+        }
+        None => assert!(false),
+    };
+}
+#[tokio::test]
+#[ignore]
 async fn test_transfer_playback() {
     let mut oauth = SpotifyOAuth::default()
         .scope("user-modify-playback-state")
@@ -760,6 +1113,33 @@ async fn test_transfer_playback() {
     };
 }
 
+#[tokio::test]
+#[ignore]
+async fn test_user_artist_check_follow_over_limit() {
+    let mut oauth = SpotifyOAuth::default().scope("user-follow-read").build();
+    match get_token(&mut oauth).await {
+        Some(token_info) => {
+            let client_credential = SpotifyClientCredentials::default()
+                .token_info(token_info)
+                .build();
+            let spotify = Spotify::default()
+                .client_credentials_manager(client_credential)
+                .build();
+            let artists: Vec<String> = ["74ASZWbe4lXaubB36ztrGX", "08td7MxkoHQkXnWAYD8d6Q"]
+                .iter()
+                .cycle()
+                .take(60)
+                .map(|id| id.to_string())
+                .collect();
+            let result = spotify.user_artist_check_follow(&artists).await.unwrap();
+            assert_eq!(result.len(), artists.len());
+            assert_eq!(result[0], result[50]);
+            assert_eq!(result[1], result[51]);
+        }
+        None => assert!(false),
+    };
+}
+
 #[tokio::test]
 #[ignore]
 async fn test_user_follow_artist() {